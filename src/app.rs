@@ -1,3 +1,4 @@
+use sdl2::controller::GameController;
 use sdl2::event::Event;
 use sdl2::image::LoadTexture;
 use sdl2::joystick::Joystick;
@@ -5,16 +6,19 @@ use sdl2::render::{Canvas, Texture, TextureCreator};
 use sdl2::video::{DisplayMode, Window, WindowBuildError, WindowContext};
 use std::collections::HashMap;
 
+use crate::gamecontroller;
 use crate::joystick::*;
 
 pub struct App {
     pub sdl_context: sdl2::Sdl,
     pub joystick: sdl2::JoystickSubsystem,
+    pub game_controller: sdl2::GameControllerSubsystem,
     pub timer: sdl2::TimerSubsystem,
     running: bool,
     pub canvas: Canvas<Window>,
     pub texture_creator: TextureCreator<WindowContext>,
     opened_joysticks: HashMap<i32, Joystick>,
+    opened_game_controllers: HashMap<i32, GameController>,
     pub display_mode: DisplayMode,
     event_pump: sdl2::EventPump,
 }
@@ -30,6 +34,8 @@ impl App {
         let video = sdl_context.video().unwrap();
         let display_mode = video.desktop_display_mode(0).unwrap();
         let joystick = sdl_context.joystick().unwrap();
+        let game_controller = sdl_context.game_controller().unwrap();
+        gamecontroller::load_mappings(&game_controller);
         let timer = sdl_context.timer().unwrap();
         let canvas = build_window(video).unwrap().into_canvas().build().unwrap();
         let texture_creator = canvas.texture_creator();
@@ -38,20 +44,22 @@ impl App {
         App {
             sdl_context,
             joystick,
+            game_controller,
             timer,
             running: true,
             canvas,
             texture_creator,
             opened_joysticks: HashMap::new(),
+            opened_game_controllers: HashMap::new(),
             display_mode,
             event_pump,
         }
     }
 
-    pub fn load_texture(&self, filepath: &str) -> Texture {
+    pub fn load_texture(&self, filepath: &str) -> Result<Texture, String> {
         self.texture_creator
             .load_texture(filepath)
-            .unwrap_or_else(|_| panic!("Couldn't load texture file: {}", filepath))
+            .map_err(|err| format!("Couldn't load texture file {}: {}", filepath, err))
     }
 
     pub fn is_running(&self) -> bool {
@@ -63,7 +71,7 @@ impl App {
         self.running = false;
     }
 
-    pub fn open_joystick(&mut self, which: u32) -> Option<JoystickInfo> {
+    pub fn open_joystick(&mut self, which: u32) -> Option<(JoystickInfo, String)> {
         match self.joystick.open(which) {
             Ok(joystick) => {
                 let id = joystick.instance_id();
@@ -80,9 +88,10 @@ impl App {
                     .filter(|x| x.guid() == joystick.guid() && x.attached())
                     .count();
                 let joystick_info = JoystickInfo::new(&joystick, index);
+                let name = joystick.name();
                 debug!("new joystick info: {:?}", joystick_info);
                 self.opened_joysticks.insert(id, joystick);
-                Some(joystick_info)
+                Some((joystick_info, name))
             }
             Err(err) => {
                 error!("could not open joystick: {}", err);
@@ -91,15 +100,56 @@ impl App {
         }
     }
 
+    /// Open a joystick as a game controller if SDL knows its mapping and return the mapping
+    pub fn open_game_controller(&mut self, which: u32) -> Option<String> {
+        if !self.game_controller.is_game_controller(which) {
+            return None;
+        }
+
+        match self.game_controller.open(which) {
+            Ok(controller) => {
+                let mapping = controller.mapping();
+                debug!("game controller mapping: {}", mapping);
+                self.opened_game_controllers
+                    .insert(controller.instance_id(), controller);
+                Some(mapping)
+            }
+            Err(err) => {
+                error!("could not open game controller: {}", err);
+                None
+            }
+        }
+    }
+
     pub fn close_joystick(&mut self, which: i32) {
         self.opened_joysticks.remove(&which);
+        self.opened_game_controllers.remove(&which);
         info!("removed joystick");
     }
 
+    /// Hide the window and release the joysticks while an emulator runs
+    pub fn suspend(&mut self) {
+        self.opened_game_controllers.clear();
+        self.opened_joysticks.clear();
+        self.canvas.window_mut().hide();
+    }
+
+    /// Show the window again and forget the events received while it was hidden
+    pub fn resume(&mut self) {
+        self.canvas.window_mut().show();
+        self.canvas.window_mut().raise();
+        while self.event_pump.poll_event().is_some() {}
+        self.running = true;
+    }
+
     pub fn wait_event(&mut self) -> Event {
         self.event_pump.wait_event()
     }
 
+    pub fn wait_event_timeout(&mut self, timeout: u32) -> Option<Event> {
+        self.event_pump.wait_event_timeout(timeout)
+    }
+
     pub fn poll_event(&mut self) -> Option<Event> {
         self.event_pump.poll_event()
     }
@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+const ARCHIVE_EXTENSIONS: [&str; 2] = ["zip", "7z"];
+
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .map(|x| ARCHIVE_EXTENSIONS.iter().any(|y| x.eq_ignore_ascii_case(y)))
+        == Some(true)
+}
+
+/// List the files stored in an archive (directories are skipped)
+pub fn list_files(path: &Path) -> Result<Vec<String>, String> {
+    let extension = path
+        .extension()
+        .and_then(|x| x.to_str())
+        .map(|x| x.to_lowercase());
+
+    match extension.as_deref() {
+        Some("zip") => {
+            let file = File::open(path).map_err(|x| format!("{}", x))?;
+            let archive = zip::ZipArchive::new(file).map_err(|x| format!("{}", x))?;
+
+            Ok(archive
+                .file_names()
+                .filter(|x| !x.ends_with('/'))
+                .map(|x| x.to_string())
+                .collect())
+        }
+        Some("7z") => {
+            let archive = sevenz_rust::Archive::open(path).map_err(|x| format!("{}", x))?;
+
+            Ok(archive
+                .files
+                .iter()
+                .filter(|x| !x.is_directory)
+                .map(|x| x.name.clone())
+                .collect())
+        }
+        _ => Err(format!("unsupported archive: {}", path.display())),
+    }
+}
+
+/// Find the first file of an archive that has one of the extensions
+pub fn find_file(path: &Path, extensions: &[String]) -> Result<Option<String>, String> {
+    let mut files = list_files(path)?;
+    files.sort();
+
+    Ok(files.into_iter().find(|x| {
+        Path::new(x)
+            .extension()
+            .and_then(|x| x.to_str())
+            .map(|x| extensions.iter().any(|y| y == x))
+            == Some(true)
+    }))
+}
+
+/// Read a file stored in an archive without loading it in memory
+pub fn read_file<T, F>(path: &Path, name: &str, read: F) -> Result<T, String>
+where
+    F: FnOnce(&mut dyn Read) -> io::Result<T>,
+{
+    let extension = path
+        .extension()
+        .and_then(|x| x.to_str())
+        .map(|x| x.to_lowercase());
+
+    match extension.as_deref() {
+        Some("zip") => {
+            let file = File::open(path).map_err(|x| format!("{}", x))?;
+            let mut archive = zip::ZipArchive::new(file).map_err(|x| format!("{}", x))?;
+            let mut file = archive.by_name(name).map_err(|x| format!("{}", x))?;
+
+            read(&mut file).map_err(|x| format!("{}", x))
+        }
+        Some("7z") => {
+            let mut reader = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty())
+                .map_err(|x| format!("{}", x))?;
+            let mut read = Some(read);
+            let mut result = None;
+            reader
+                .for_each_entries(|entry, file| {
+                    if entry.name != name {
+                        return Ok(true);
+                    }
+
+                    result = read.take().map(|x| x(file)).transpose()?;
+                    Ok(false)
+                })
+                .map_err(|x| format!("{}", x))?;
+
+            result.ok_or_else(|| format!("file not found in archive: {}", name))
+        }
+        _ => Err(format!("unsupported archive: {}", path.display())),
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::joystick::JoystickGuid;
+use crate::store::{AxisState, HatState, JoystickEvent};
+
+/// Find the RetroArch autoconfig profile of a joystick by its vendor and product ids and read the
+/// events of its controls
+pub fn find(folders: &[String], guid: &JoystickGuid) -> Option<HashMap<String, JoystickEvent>> {
+    let (vendor, product) = guid.vendor_product()?;
+
+    for folder in folders {
+        let resolved_dir = folder.replace("~", dirs::home_dir().unwrap().to_str().unwrap());
+        let entries = match fs::read_dir(&resolved_dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!("could not read autoconfig folder {}: {}", resolved_dir, err);
+                continue;
+            }
+        };
+
+        for path in entries.filter_map(|x| x.ok()).map(|x| x.path()) {
+            if path.extension().and_then(|x| x.to_str()) != Some("cfg") {
+                continue;
+            }
+
+            let values = match read_file(&path) {
+                Ok(values) => values,
+                Err(err) => {
+                    warn!("could not read {}: {}", path.display(), err);
+                    continue;
+                }
+            };
+            let id = |key| values.get(key).and_then(|x: &String| x.parse::<u16>().ok());
+            if id("input_vendor_id") == Some(vendor) && id("input_product_id") == Some(product) {
+                info!("using autoconfig profile {}", path.display());
+                return Some(parse_controls(&values));
+            }
+        }
+    }
+
+    None
+}
+
+/// Read the values of a configuration file of RetroArch: `input_b_btn = "0"`
+fn read_file(path: &Path) -> Result<HashMap<String, String>, String> {
+    let content = fs::read_to_string(path).map_err(|x| format!("{}", x))?;
+
+    Ok(content
+        .lines()
+        .filter(|x| !x.trim_start().starts_with('#'))
+        .filter_map(|x| x.split_once('='))
+        .map(|(key, value)| {
+            (
+                key.trim().to_string(),
+                value.trim().trim_matches('"').to_string(),
+            )
+        })
+        .collect())
+}
+
+/// The events of the controls of a profile: "input_up_btn = h0up" gives the hat 0 up to "up",
+/// "input_l_x_plus_axis = +0" gives the axis 0 to "l_x_plus". The buttons are preferred to the
+/// axes when a control has both
+fn parse_controls(values: &HashMap<String, String>) -> HashMap<String, JoystickEvent> {
+    let mut controls = HashMap::new();
+
+    for (key, value) in values.iter() {
+        let control = match key.strip_prefix("input_") {
+            Some(x) => x,
+            None => continue,
+        };
+
+        if let Some(control) = control.strip_suffix("_btn") {
+            if let Some(event) = parse_button(value) {
+                controls.insert(control.to_string(), event);
+            }
+        } else if let Some(control) = control.strip_suffix("_axis") {
+            if let Some(event) = parse_axis(value) {
+                controls.entry(control.to_string()).or_insert(event);
+            }
+        }
+    }
+
+    controls
+}
+
+/// Parse a button ("3") or a direction of a hat ("h0up")
+fn parse_button(value: &str) -> Option<JoystickEvent> {
+    if let Some(hat) = value.strip_prefix('h') {
+        let index = hat.find(|c: char| !c.is_ascii_digit())?;
+        let state = match &hat[index..] {
+            "up" => HatState::Up,
+            "down" => HatState::Down,
+            "left" => HatState::Left,
+            "right" => HatState::Right,
+            _ => return None,
+        };
+
+        return hat[..index]
+            .parse()
+            .ok()
+            .map(|x| JoystickEvent::Hat(x, state));
+    }
+
+    value.parse().ok().map(JoystickEvent::Button)
+}
+
+/// Parse a direction of an axis: "+2", "-1"
+fn parse_axis(value: &str) -> Option<JoystickEvent> {
+    let (state, axis) = if let Some(x) = value.strip_prefix('+') {
+        (AxisState::Positive, x)
+    } else {
+        (AxisState::Negative, value.strip_prefix('-')?)
+    };
+
+    axis.parse().ok().map(|x| JoystickEvent::Axis(x, state))
+}
+
+/// Write an autoconfig profile of RetroArch for a joystick, named after the joystick and its ids.
+/// The indices of the controls are SDL's, so the profile is for the "sdl2" joypad driver
+pub fn export(dir: &Path, name: &str, guid: &JoystickGuid, lines: &[String]) -> Result<(), String> {
+    let ids = match guid.vendor_product() {
+        Some((vendor, product)) => format!("{:04x}_{:04x}", vendor, product),
+        None => guid.0.iter().map(|x| format!("{:02x}", x)).collect(),
+    };
+    let file_name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let path = dir.join(format!("{}_{}.cfg", file_name, ids));
+    let mut content = format!("input_driver = \"sdl2\"\ninput_device = \"{}\"\n", name);
+
+    if let Some((vendor, product)) = guid.vendor_product() {
+        content.push_str(&format!(
+            "input_vendor_id = \"{}\"\ninput_product_id = \"{}\"\n",
+            vendor, product
+        ));
+    }
+    for line in lines {
+        content.push_str(line);
+        content.push('\n');
+    }
+
+    fs::create_dir_all(dir).map_err(|x| format!("{}", x))?;
+    fs::write(&path, content).map_err(|x| format!("{}", x))?;
+    debug!("autoconfig profile written to {}", path.display());
+
+    Ok(())
+}
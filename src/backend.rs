@@ -0,0 +1,679 @@
+use std::path::Path;
+
+use crate::joystick::JoystickInfo;
+use crate::keyboard::KEYBOARD_ID;
+use crate::store::{AxisState, Emulator, HatState, JoystickEvent};
+
+/// The controls of a player given to a backend
+pub struct PlayerControls<'a> {
+    /// Index of the joystick among the plugged joysticks, the keyboard has an index no joystick
+    /// uses
+    pub joypad_index: usize,
+    pub joystick: &'a JoystickInfo,
+    /// The events of the controls of the emulator: ("a", Button(1))
+    pub controls: Vec<(&'a str, &'a JoystickEvent)>,
+    /// The hotkeys of RetroArch (only for the first player): ("enable_hotkey", Button(8))
+    pub hotkeys: Vec<(&'a str, &'a JoystickEvent)>,
+}
+
+/// Turns the controls of the players into the configuration of an emulator
+pub trait Backend {
+    /// Content of the configuration file given to the emulator
+    fn config(&self, emulator: &Emulator, players: &[PlayerControls]) -> String;
+
+    /// Add the lines of the overrides of the game to the configuration file
+    fn extra_config(&self, config: &mut String, lines: &[String]) {
+        for line in lines {
+            config.push_str(line);
+            config.push('\n');
+        }
+    }
+
+    /// Arguments added to the command of the emulator when it has no placeholders
+    fn args(&self, config_path: &str, config: &str, rom: &str) -> Vec<String>;
+
+    /// Environment variables of the emulator
+    fn env(&self, _emulator: &Emulator, _players: &[PlayerControls]) -> Vec<(String, String)> {
+        Vec::new()
+    }
+}
+
+/// The backend of an emulator in 'state.json'
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum BackendKind {
+    #[default]
+    RetroArch,
+    Mednafen,
+    Mame,
+    Env,
+}
+
+impl BackendKind {
+    pub fn get(self) -> Box<dyn Backend> {
+        match self {
+            BackendKind::RetroArch => Box::new(RetroArch),
+            BackendKind::Mednafen => Box::new(Mednafen),
+            BackendKind::Mame => Box::new(Mame),
+            BackendKind::Env => Box::new(Env),
+        }
+    }
+}
+
+/// The end of a line of RetroArch's configuration: "_btn = 1", "_btn = h0up", "_axis = +0" or
+/// " = enter" for a key
+pub fn retroarch_binding(event: &JoystickEvent) -> String {
+    use self::JoystickEvent::*;
+
+    match *event {
+        Button(x) => format!("_btn = {}", x),
+        Hat(x, ref state) => format!(
+            "_btn = h{}{}",
+            x,
+            match *state {
+                HatState::Up => "up",
+                HatState::Down => "down",
+                HatState::Left => "left",
+                HatState::Right => "right",
+            }
+        ),
+        Axis(x, AxisState::Positive) => format!("_axis = +{}", x),
+        Axis(x, AxisState::Negative) => format!("_axis = -{}", x),
+        Key(ref x) => format!(" = {}", x),
+        Unassigned => panic!(),
+    }
+}
+
+/// RetroArch's `--appendconfig` file: `input_player1_a_btn = 1`
+struct RetroArch;
+
+impl Backend for RetroArch {
+    fn config(&self, _emulator: &Emulator, players: &[PlayerControls]) -> String {
+        let mut config = String::new();
+
+        for (i, player) in players.iter().enumerate() {
+            // NOTE: otherwise RetroArch gives the index of the player to the keyboard's player
+            config.push_str(&format!(
+                "input_player{}_joypad_index = {}\n",
+                i + 1,
+                player.joypad_index
+            ));
+
+            for &(control, event) in player.controls.iter() {
+                config.push_str(&match *event {
+                    JoystickEvent::Unassigned => {
+                        format!("// input_player{}_{} unassigned\n", i + 1, control)
+                    }
+                    _ => format!(
+                        "input_player{}_{}{}\n",
+                        i + 1,
+                        control,
+                        retroarch_binding(event)
+                    ),
+                });
+            }
+
+            for &(hotkey, event) in player.hotkeys.iter() {
+                config.push_str(&format!("input_{}{}\n", hotkey, retroarch_binding(event)));
+            }
+
+            config.push('\n');
+        }
+
+        config.push_str("config_save_on_exit = false\n");
+
+        config
+    }
+
+    fn args(&self, config_path: &str, _config: &str, rom: &str) -> Vec<String> {
+        vec![
+            "--appendconfig".to_string(),
+            config_path.to_string(),
+            rom.to_string(),
+        ]
+    }
+}
+
+/// Settings of Mednafen given on its command line: `-md.input.port1.gamepad.a "joystick 0x...
+/// button_1"`. The controls of the emulator are named like Mednafen's ("a", "start", "i", "run",
+/// ...) and its id is Mednafen's system ("md", "pce", ...). The keys are not supported
+struct Mednafen;
+
+impl Mednafen {
+    /// The id of a joystick in Mednafen: the SDL GUID, with the last 16 bits increased for each
+    /// joystick plugged before with the same GUID
+    fn device(player: &PlayerControls) -> String {
+        let mut id = player.joystick.guid.0;
+        let counter =
+            u16::from_be_bytes([id[14], id[15]]).wrapping_add(player.joystick.index as u16);
+        id[14..].copy_from_slice(&counter.to_be_bytes());
+
+        format!(
+            "0x{}",
+            id.iter().map(|x| format!("{:02x}", x)).collect::<String>()
+        )
+    }
+
+    /// The hats are the buttons following the real buttons of the joystick: up, right, down
+    /// and left
+    fn binding(player: &PlayerControls, event: &JoystickEvent) -> Option<String> {
+        use self::JoystickEvent::*;
+
+        let binding = match *event {
+            Button(x) => format!("button_{}", x),
+            Hat(x, ref state) => format!(
+                "button_{}",
+                player.joystick.buttons
+                    + x as u32 * 4
+                    + match *state {
+                        HatState::Up => 0,
+                        HatState::Right => 1,
+                        HatState::Down => 2,
+                        HatState::Left => 3,
+                    }
+            ),
+            Axis(x, AxisState::Positive) => format!("abs_{}+", x),
+            Axis(x, AxisState::Negative) => format!("abs_{}-", x),
+            Key(_) | Unassigned => return None,
+        };
+
+        Some(binding)
+    }
+}
+
+impl Backend for Mednafen {
+    fn config(&self, emulator: &Emulator, players: &[PlayerControls]) -> String {
+        let mut config = String::new();
+
+        for (i, player) in players.iter().enumerate() {
+            let device = Self::device(player);
+
+            for &(control, event) in player.controls.iter() {
+                let setting = format!("{}.input.port{}.gamepad.{}", emulator.id, i + 1, control);
+
+                config.push_str(&match Self::binding(player, event) {
+                    Some(binding) => format!("{} joystick {} {}\n", setting, device, binding),
+                    None => format!(";{} not assigned\n", setting),
+                });
+            }
+        }
+
+        config
+    }
+
+    fn args(&self, _config_path: &str, config: &str, rom: &str) -> Vec<String> {
+        let mut args = Vec::new();
+
+        for (setting, value) in config
+            .lines()
+            .filter(|x| !x.starts_with(';'))
+            .filter_map(|x| x.split_once(' '))
+        {
+            args.push(format!("-{}", setting));
+            args.push(value.to_string());
+        }
+        args.push(rom.to_string());
+
+        args
+    }
+}
+
+/// A controller configuration file of MAME, loaded with `-ctrlrpath` and `-ctrlr`. The controls of
+/// the emulator are MAME's input types, prefixed by the player ("BUTTON1" is "P1_BUTTON1") unless
+/// they have a `#` replaced by the player ("START#" is "START1")
+struct Mame;
+
+const MAME_INPUT_END: &str = "        </input>\n";
+
+impl Mame {
+    fn code(player: &PlayerControls, event: &JoystickEvent) -> Option<String> {
+        use self::JoystickEvent::*;
+
+        let joystick = player.joypad_index + 1;
+        let code = match *event {
+            Button(x) => format!("JOYCODE_{}_BUTTON{}", joystick, x + 1),
+            Hat(x, ref state) => format!(
+                "JOYCODE_{}_HAT{}{}",
+                joystick,
+                x + 1,
+                format!("{:?}", state).to_uppercase()
+            ),
+            Axis(x, ref state) => {
+                let axis = ["X", "Y", "Z", "RX", "RY", "RZ"].get(x as usize)?;
+                let direction = match (x % 2 == 0, state) {
+                    (true, AxisState::Negative) => "LEFT",
+                    (true, AxisState::Positive) => "RIGHT",
+                    (false, AxisState::Negative) => "UP",
+                    (false, AxisState::Positive) => "DOWN",
+                };
+
+                format!("JOYCODE_{}_{}AXIS_{}_SWITCH", joystick, axis, direction)
+            }
+            Key(ref x) => format!("KEYCODE_{}", Self::key(x)?),
+            Unassigned => return None,
+        };
+
+        Some(code)
+    }
+
+    /// The name of a key in MAME from its name in RetroArch
+    fn key(key: &str) -> Option<String> {
+        if key.len() == 1 {
+            return Some(key.to_uppercase());
+        } else if let Some(digit) = key.strip_prefix("num") {
+            return Some(digit.to_string());
+        }
+
+        let key = match key {
+            "up" => "UP",
+            "down" => "DOWN",
+            "left" => "LEFT",
+            "right" => "RIGHT",
+            "enter" => "ENTER",
+            "space" => "SPACE",
+            "tab" => "TAB",
+            "backspace" => "BACKSPACE",
+            "shift" => "LSHIFT",
+            "rshift" => "RSHIFT",
+            "ctrl" => "LCONTROL",
+            "rctrl" => "RCONTROL",
+            "alt" => "LALT",
+            "ralt" => "RALT",
+            "insert" => "INSERT",
+            "del" => "DEL",
+            "home" => "HOME",
+            "end" => "END",
+            "pageup" => "PGUP",
+            "pagedown" => "PGDN",
+            "comma" => "COMMA",
+            "period" => "STOP",
+            "slash" => "SLASH",
+            "semicolon" => "COLON",
+            "minus" => "MINUS",
+            "equals" => "EQUALS",
+            "leftbracket" => "OPENBRACE",
+            "rightbracket" => "CLOSEBRACE",
+            "backslash" => "BACKSLASH",
+            "quote" => "QUOTE",
+            "backquote" => "TILDE",
+            "f1" => "F1",
+            "f2" => "F2",
+            "f3" => "F3",
+            "f4" => "F4",
+            "f5" => "F5",
+            "f6" => "F6",
+            "f7" => "F7",
+            "f8" => "F8",
+            "f9" => "F9",
+            "f10" => "F10",
+            "f11" => "F11",
+            "f12" => "F12",
+            _ => return None,
+        };
+
+        Some(key.to_string())
+    }
+}
+
+impl Backend for Mame {
+    fn config(&self, _emulator: &Emulator, players: &[PlayerControls]) -> String {
+        let mut config = "<?xml version=\"1.0\"?>\n<mameconfig version=\"10\">\n    <system name=\"default\">\n        <input>\n".to_string();
+
+        for (i, player) in players.iter().enumerate() {
+            for &(control, event) in player.controls.iter() {
+                let port = if control.contains('#') {
+                    control.replace('#', &(i + 1).to_string())
+                } else {
+                    format!("P{}_{}", i + 1, control)
+                };
+
+                if let Some(code) = Self::code(player, event) {
+                    config.push_str(&format!(
+                        "            <port type=\"{}\">\n                <newseq type=\"standard\">{}</newseq>\n            </port>\n",
+                        port, code
+                    ));
+                }
+            }
+        }
+
+        config.push_str(MAME_INPUT_END);
+        config.push_str("    </system>\n</mameconfig>\n");
+
+        config
+    }
+
+    /// The lines are elements of the input section: `<port type="UI_PAUSE">...</port>`
+    fn extra_config(&self, config: &mut String, lines: &[String]) {
+        let end = config.rfind(MAME_INPUT_END).unwrap_or(config.len());
+        let extra: String = lines
+            .iter()
+            .map(|x| format!("            {}\n", x))
+            .collect();
+
+        config.insert_str(end, &extra);
+    }
+
+    fn args(&self, config_path: &str, _config: &str, rom: &str) -> Vec<String> {
+        let path = Path::new(config_path);
+
+        vec![
+            "-ctrlrpath".to_string(),
+            path.parent()
+                .and_then(|x| x.to_str())
+                .unwrap_or_default()
+                .to_string(),
+            "-ctrlr".to_string(),
+            path.file_stem()
+                .and_then(|x| x.to_str())
+                .unwrap_or_default()
+                .to_string(),
+            rom.to_string(),
+        ]
+    }
+}
+
+/// Environment variables for scripts: `ROM_LAUNCHER_PLAYER1_A=button:1`, `..._UP=hat:0:up`,
+/// `..._LEFT=axis:0:-` or `..._START=key:enter`. The configuration file has the same variables
+struct Env;
+
+impl Backend for Env {
+    fn config(&self, emulator: &Emulator, players: &[PlayerControls]) -> String {
+        self.env(emulator, players)
+            .into_iter()
+            .map(|(name, value)| format!("{}={}\n", name, value))
+            .collect()
+    }
+
+    fn args(&self, _config_path: &str, _config: &str, rom: &str) -> Vec<String> {
+        vec![rom.to_string()]
+    }
+
+    fn env(&self, emulator: &Emulator, players: &[PlayerControls]) -> Vec<(String, String)> {
+        use self::JoystickEvent::*;
+
+        let mut env = vec![
+            ("ROM_LAUNCHER_EMULATOR".to_string(), emulator.id.clone()),
+            (
+                "ROM_LAUNCHER_PLAYERS".to_string(),
+                players.len().to_string(),
+            ),
+        ];
+
+        for (i, player) in players.iter().enumerate() {
+            let prefix = format!("ROM_LAUNCHER_PLAYER{}", i + 1);
+
+            if player.joystick.instance_id != KEYBOARD_ID {
+                env.push((
+                    format!("{}_JOYSTICK", prefix),
+                    player.joypad_index.to_string(),
+                ));
+            }
+            for &(control, event) in player.controls.iter() {
+                let value = match *event {
+                    Button(x) => format!("button:{}", x),
+                    Hat(x, ref state) => {
+                        format!("hat:{}:{}", x, format!("{:?}", state).to_lowercase())
+                    }
+                    Axis(x, AxisState::Positive) => format!("axis:{}:+", x),
+                    Axis(x, AxisState::Negative) => format!("axis:{}:-", x),
+                    Key(ref x) => format!("key:{}", x),
+                    Unassigned => continue,
+                };
+
+                env.push((format!("{}_{}", prefix, control.to_uppercase()), value));
+            }
+        }
+
+        env
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joystick::JoystickGuid;
+    use crate::keyboard::keyboard_info;
+    use crate::store::JoystickEvent::*;
+
+    fn emulator() -> Emulator {
+        serde_json::from_str(
+            r#"{"id": "snes", "name": "SNES", "path": "~/roms", "controls": [],
+                "command": ["retroarch"], "extensions": [], "exclude": []}"#,
+        )
+        .unwrap()
+    }
+
+    fn joystick(index: usize) -> JoystickInfo {
+        JoystickInfo {
+            instance_id: 0,
+            guid: JoystickGuid([
+                0x03, 0x00, 0x00, 0x00, 0x5e, 0x04, 0x00, 0x00, 0x8e, 0x02, 0x00, 0x00, 0x14, 0x01,
+                0x00, 0x00,
+            ]),
+            index,
+            split: 1,
+            buttons: 11,
+        }
+    }
+
+    fn port(port: &str, code: &str) -> String {
+        format!(
+            "            <port type=\"{}\">\n                <newseq type=\"standard\">{}</newseq>\n            </port>\n",
+            port, code
+        )
+    }
+
+    #[test]
+    fn retroarch_config() {
+        let (a, b, up, right, hotkey) = (
+            Button(1),
+            Unassigned,
+            Hat(0, HatState::Up),
+            Axis(0, AxisState::Positive),
+            Button(8),
+        );
+        let key = Key("x".to_string());
+        let (pad, keyboard) = (joystick(0), keyboard_info());
+        let players = [
+            PlayerControls {
+                joypad_index: 0,
+                joystick: &pad,
+                controls: vec![("a", &a), ("b", &b), ("up", &up), ("l_x_plus", &right)],
+                hotkeys: vec![("enable_hotkey", &hotkey)],
+            },
+            PlayerControls {
+                joypad_index: 1,
+                joystick: &keyboard,
+                controls: vec![("a", &key)],
+                hotkeys: vec![],
+            },
+        ];
+
+        assert_eq!(
+            RetroArch.config(&emulator(), &players),
+            "input_player1_joypad_index = 0\n\
+             input_player1_a_btn = 1\n\
+             // input_player1_b unassigned\n\
+             input_player1_up_btn = h0up\n\
+             input_player1_l_x_plus_axis = +0\n\
+             input_enable_hotkey_btn = 8\n\
+             \n\
+             input_player2_joypad_index = 1\n\
+             input_player2_a = x\n\
+             \n\
+             config_save_on_exit = false\n"
+        );
+        assert_eq!(
+            RetroArch.args("/tmp/a.cfg", "", "rom.sfc"),
+            ["--appendconfig", "/tmp/a.cfg", "rom.sfc"]
+        );
+    }
+
+    #[test]
+    fn mame_config() {
+        let (button, start, up, left, unassigned) = (
+            Button(0),
+            Button(7),
+            Hat(0, HatState::Up),
+            Axis(0, AxisState::Negative),
+            Unassigned,
+        );
+        let key = Key("x".to_string());
+        let (pad, keyboard) = (joystick(0), keyboard_info());
+        let players = [
+            PlayerControls {
+                joypad_index: 0,
+                joystick: &pad,
+                controls: vec![
+                    ("BUTTON1", &button),
+                    ("START#", &start),
+                    ("UP", &up),
+                    ("LEFT", &left),
+                    ("BUTTON2", &unassigned),
+                ],
+                hotkeys: vec![],
+            },
+            PlayerControls {
+                joypad_index: 1,
+                joystick: &keyboard,
+                controls: vec![("BUTTON1", &key)],
+                hotkeys: vec![],
+            },
+        ];
+        let keys = [
+            ("x", "KEYCODE_X"),
+            ("num1", "KEYCODE_1"),
+            ("shift", "KEYCODE_LSHIFT"),
+            ("ctrl", "KEYCODE_LCONTROL"),
+            ("alt", "KEYCODE_LALT"),
+            ("rctrl", "KEYCODE_RCONTROL"),
+            ("pageup", "KEYCODE_PGUP"),
+            ("pagedown", "KEYCODE_PGDN"),
+            ("leftbracket", "KEYCODE_OPENBRACE"),
+            ("period", "KEYCODE_STOP"),
+            ("semicolon", "KEYCODE_COLON"),
+            ("backquote", "KEYCODE_TILDE"),
+            ("f12", "KEYCODE_F12"),
+        ];
+        for (key, code) in keys {
+            let event = Key(key.to_string());
+            assert_eq!(Mame::code(&players[1], &event).as_deref(), Some(code));
+        }
+        assert_eq!(Mame::code(&players[1], &Key("menu".to_string())), None);
+
+        let mut config = Mame.config(&emulator(), &players);
+        Mame.extra_config(&mut config, &["<port type=\"UI_PAUSE\"/>".to_string()]);
+        assert_eq!(
+            config,
+            format!(
+                "<?xml version=\"1.0\"?>\n<mameconfig version=\"10\">\n    <system name=\"default\">\n        <input>\n{}{}{}{}{}            <port type=\"UI_PAUSE\"/>\n        </input>\n    </system>\n</mameconfig>\n",
+                port("P1_BUTTON1", "JOYCODE_1_BUTTON1"),
+                port("START1", "JOYCODE_1_BUTTON8"),
+                port("P1_UP", "JOYCODE_1_HAT1UP"),
+                port("P1_LEFT", "JOYCODE_1_XAXIS_LEFT_SWITCH"),
+                port("P2_BUTTON1", "KEYCODE_X"),
+            )
+        );
+        assert_eq!(
+            Mame.args("/tmp/abc.cfg", "", "rom.zip"),
+            ["-ctrlrpath", "/tmp", "-ctrlr", "abc", "rom.zip"]
+        );
+    }
+
+    #[test]
+    fn mednafen_config() {
+        let (a, b, up, left, right) = (
+            Button(1),
+            Unassigned,
+            Hat(0, HatState::Up),
+            Hat(0, HatState::Left),
+            Axis(0, AxisState::Positive),
+        );
+        let key = Key("x".to_string());
+        let (first, second, keyboard) = (joystick(0), joystick(1), keyboard_info());
+        let players = [
+            PlayerControls {
+                joypad_index: 0,
+                joystick: &first,
+                controls: vec![("a", &a), ("b", &b), ("up", &up), ("left", &left)],
+                hotkeys: vec![],
+            },
+            PlayerControls {
+                joypad_index: 1,
+                joystick: &second,
+                controls: vec![("right", &right)],
+                hotkeys: vec![],
+            },
+            PlayerControls {
+                joypad_index: 2,
+                joystick: &keyboard,
+                controls: vec![("a", &key)],
+                hotkeys: vec![],
+            },
+        ];
+
+        let config = Mednafen.config(&emulator(), &players);
+        assert_eq!(
+            config,
+            "snes.input.port1.gamepad.a joystick 0x030000005e0400008e02000014010000 button_1\n\
+             ;snes.input.port1.gamepad.b not assigned\n\
+             snes.input.port1.gamepad.up joystick 0x030000005e0400008e02000014010000 button_11\n\
+             snes.input.port1.gamepad.left joystick 0x030000005e0400008e02000014010000 button_14\n\
+             snes.input.port2.gamepad.right joystick 0x030000005e0400008e02000014010001 abs_0+\n\
+             ;snes.input.port3.gamepad.a not assigned\n"
+        );
+        assert_eq!(
+            Mednafen.args("/tmp/a.cfg", &config, "rom.sfc")[..4],
+            [
+                "-snes.input.port1.gamepad.a",
+                "joystick 0x030000005e0400008e02000014010000 button_1",
+                "-snes.input.port1.gamepad.up",
+                "joystick 0x030000005e0400008e02000014010000 button_11",
+            ]
+        );
+        assert_eq!(
+            Mednafen
+                .args("/tmp/a.cfg", &config, "rom.sfc")
+                .last()
+                .unwrap(),
+            "rom.sfc"
+        );
+    }
+
+    #[test]
+    fn env_config() {
+        let (a, b, up, right) = (
+            Button(1),
+            Unassigned,
+            Hat(0, HatState::Up),
+            Axis(0, AxisState::Positive),
+        );
+        let key = Key("x".to_string());
+        let (pad, keyboard) = (joystick(0), keyboard_info());
+        let players = [
+            PlayerControls {
+                joypad_index: 0,
+                joystick: &pad,
+                controls: vec![("a", &a), ("b", &b), ("up", &up), ("l_x_plus", &right)],
+                hotkeys: vec![],
+            },
+            PlayerControls {
+                joypad_index: 1,
+                joystick: &keyboard,
+                controls: vec![("a", &key)],
+                hotkeys: vec![],
+            },
+        ];
+
+        assert_eq!(
+            Env.config(&emulator(), &players),
+            "ROM_LAUNCHER_EMULATOR=snes\n\
+             ROM_LAUNCHER_PLAYERS=2\n\
+             ROM_LAUNCHER_PLAYER1_JOYSTICK=0\n\
+             ROM_LAUNCHER_PLAYER1_A=button:1\n\
+             ROM_LAUNCHER_PLAYER1_UP=hat:0:up\n\
+             ROM_LAUNCHER_PLAYER1_L_X_PLUS=axis:0:+\n\
+             ROM_LAUNCHER_PLAYER2_A=key:x\n"
+        );
+        assert_eq!(Env.args("/tmp/a.cfg", "", "rom.sfc"), ["rom.sfc"]);
+    }
+}
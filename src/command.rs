@@ -0,0 +1,240 @@
+use std::path::Path;
+
+/// Placeholders that can be used in the command of an emulator
+pub const PLACEHOLDERS: [&str; 6] = [
+    "rom",
+    "rom_dir",
+    "rom_stem",
+    "config",
+    "player_count",
+    "emulator_id",
+];
+
+enum Token {
+    Text(String),
+    Placeholder(String),
+}
+
+/// Split an argument of a command in text and placeholders: "--config={config}". The braces are
+/// written "{{" and "}}"
+fn tokenize(arg: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut chars = arg.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(x) => name.push(x),
+                        None => return Err(format!("missing '}}' in '{}'", arg)),
+                    }
+                }
+                if !PLACEHOLDERS.contains(&name.as_str()) {
+                    return Err(format!("unknown placeholder '{}' in '{}'", name, arg));
+                }
+
+                if !text.is_empty() {
+                    tokens.push(Token::Text(text.split_off(0)));
+                }
+                tokens.push(Token::Placeholder(name));
+            }
+            '}' => return Err(format!("unexpected '}}' in '{}'", arg)),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+
+    Ok(tokens)
+}
+
+/// Check the placeholders of a command, tells if it uses any
+pub fn has_placeholders(command: &[String]) -> Result<bool, String> {
+    if command.is_empty() {
+        return Err("the command is empty".to_string());
+    }
+
+    let mut found = false;
+
+    for arg in command {
+        found |= tokenize(arg)?
+            .iter()
+            .any(|x| matches!(x, Token::Placeholder(_)));
+    }
+
+    Ok(found)
+}
+
+/// The arguments of a command with the placeholders replaced. Every argument stays one argument
+/// whatever the values contain (no shell is involved). The playlist of a game with multiple discs
+/// replaces the ROM, the folder and the stem are still the ROM's. Without placeholders, the
+/// default arguments of the backend are added
+pub fn build(
+    command: &[String],
+    rom: &str,
+    playlist: Option<&str>,
+    config: &str,
+    player_count: usize,
+    emulator_id: &str,
+    default_args: Vec<String>,
+) -> Result<Vec<String>, String> {
+    if !has_placeholders(command)? {
+        let mut args = command.to_vec();
+        args.extend(default_args);
+
+        return Ok(args);
+    }
+
+    let path = Path::new(rom);
+    let value = |name: &str| match name {
+        "rom" => playlist.unwrap_or(rom).to_string(),
+        "rom_dir" => path
+            .parent()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default()
+            .to_string(),
+        "rom_stem" => path
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default()
+            .to_string(),
+        "config" => config.to_string(),
+        "player_count" => player_count.to_string(),
+        "emulator_id" => emulator_id.to_string(),
+        _ => unreachable!(),
+    };
+
+    command
+        .iter()
+        .map(|arg| {
+            Ok(tokenize(arg)?
+                .into_iter()
+                .map(|x| match x {
+                    Token::Text(text) => text,
+                    Token::Placeholder(name) => value(&name),
+                })
+                .collect())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn build_command(command: &[&str], playlist: Option<&str>) -> Result<Vec<String>, String> {
+        build(
+            &strings(command),
+            "/roms/snes/Some Game (USA).sfc",
+            playlist,
+            "/tmp/config.cfg",
+            2,
+            "snes",
+            strings(&["--appendconfig", "/tmp/config.cfg", "rom"]),
+        )
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            build_command(
+                &[
+                    "emu",
+                    "{rom}",
+                    "--dir={rom_dir}",
+                    "{rom_stem}.sav",
+                    "-c{config}",
+                    "-p{player_count}",
+                    "{emulator_id}"
+                ],
+                None
+            ),
+            Ok(strings(&[
+                "emu",
+                "/roms/snes/Some Game (USA).sfc",
+                "--dir=/roms/snes",
+                "Some Game (USA).sav",
+                "-c/tmp/config.cfg",
+                "-p2",
+                "snes"
+            ]))
+        );
+    }
+
+    #[test]
+    fn playlist() {
+        assert_eq!(
+            build_command(
+                &["emu", "{rom}", "{rom_dir}", "{rom_stem}"],
+                Some("/tmp/abc.m3u")
+            ),
+            Ok(strings(&[
+                "emu",
+                "/tmp/abc.m3u",
+                "/roms/snes",
+                "Some Game (USA)"
+            ]))
+        );
+    }
+
+    #[test]
+    fn default_args() {
+        assert_eq!(
+            build_command(&["retroarch", "-L", "core.so"], None),
+            Ok(strings(&[
+                "retroarch",
+                "-L",
+                "core.so",
+                "--appendconfig",
+                "/tmp/config.cfg",
+                "rom"
+            ]))
+        );
+    }
+
+    #[test]
+    fn escaped_braces() {
+        assert_eq!(
+            build_command(&["emu", "{{rom}}", "{{{rom_stem}}}", "a}}b"], None),
+            Ok(strings(&["emu", "{rom}", "{Some Game (USA)}", "a}b"]))
+        );
+        assert_eq!(has_placeholders(&strings(&["emu", "{{rom}}"])), Ok(false));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            build_command(&["emu", "{unknown}"], None),
+            Err("unknown placeholder 'unknown' in '{unknown}'".to_string())
+        );
+        assert_eq!(
+            build_command(&["emu", "{rom"], None),
+            Err("missing '}' in '{rom'".to_string())
+        );
+        assert_eq!(
+            build_command(&["emu", "rom}"], None),
+            Err("unexpected '}' in 'rom}'".to_string())
+        );
+        assert_eq!(
+            build_command(&[], None),
+            Err("the command is empty".to_string())
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::{File, Metadata};
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::UNIX_EPOCH;
+
+use crate::archive;
+
+const HASH_CACHE_FILE: &str = "hashes.json";
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+thread_local! {
+    /// The DAT files already parsed, by path, with their modification time
+    static DAT_CACHE: RefCell<HashMap<String, (u64, Rc<Dat>)>> = RefCell::new(HashMap::new());
+}
+
+/// Result of the verification of a ROM against the DAT files of its emulator
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum RomStatus {
+    /// The ROM is not described in the DAT files
+    #[default]
+    Unknown,
+    /// The hashes of the ROM match a good dump of the DAT files
+    Verified,
+    /// The file name is known by the DAT files but the hashes don't match
+    Bad,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Hashes {
+    pub size: u64,
+    pub crc32: String,
+    pub md5: String,
+    pub sha1: String,
+}
+
+impl Hashes {
+    /// Hash a content read by chunks, so big CD images are not loaded in memory
+    pub fn compute(reader: &mut dyn Read) -> io::Result<Hashes> {
+        let mut buffer = vec![0; HASH_BUFFER_SIZE];
+        let mut size = 0;
+        let mut crc32 = crc32fast::Hasher::new();
+        let mut md5 = md5::Context::new();
+        let mut sha1 = sha1_smol::Sha1::new();
+
+        loop {
+            let count = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(count) => count,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            size += count as u64;
+            crc32.update(&buffer[..count]);
+            md5.consume(&buffer[..count]);
+            sha1.update(&buffer[..count]);
+        }
+
+        Ok(Hashes {
+            size,
+            crc32: format!("{:08x}", crc32.finalize()),
+            md5: format!("{:x}", md5.compute()),
+            sha1: sha1.digest().to_string(),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct DatRom {
+    game: String,
+    size: Option<u64>,
+    crc32: Option<String>,
+    md5: Option<String>,
+    sha1: Option<String>,
+}
+
+impl DatRom {
+    fn matches(&self, hashes: &Hashes) -> bool {
+        self.size.is_none_or(|x| x == hashes.size)
+            && self.crc32.as_ref().is_none_or(|x| *x == hashes.crc32)
+            && self.md5.as_ref().is_none_or(|x| *x == hashes.md5)
+            && self.sha1.as_ref().is_none_or(|x| *x == hashes.sha1)
+    }
+}
+
+/// The ROMs described in Logiqx XML DAT files (No-Intro, Redump, ...)
+#[derive(Debug, Default)]
+pub struct Dat {
+    roms: Vec<DatRom>,
+    by_crc32: HashMap<String, Vec<usize>>,
+    by_name: HashMap<String, usize>,
+}
+
+impl Dat {
+    /// Parse the DAT files, or reuse them if they didn't change since they were parsed
+    pub fn load(paths: &[String]) -> Vec<Rc<Dat>> {
+        let mut dats = Vec::new();
+
+        for path in paths {
+            let resolved_path = path.replace("~", dirs::home_dir().unwrap().to_str().unwrap());
+            match Dat::load_file(&resolved_path) {
+                Ok(dat) => dats.push(dat),
+                Err(err) => warn!("could not load DAT file {}: {}", resolved_path, err),
+            }
+        }
+
+        dats
+    }
+
+    fn load_file(path: &str) -> Result<Rc<Dat>, String> {
+        let mtime = modified(&fs::metadata(path).map_err(|x| format!("{}", x))?);
+        let cached = DAT_CACHE.with(|cache| {
+            cache
+                .borrow()
+                .get(path)
+                .filter(|x| x.0 == mtime)
+                .map(|x| x.1.clone())
+        });
+        if let Some(dat) = cached {
+            return Ok(dat);
+        }
+
+        let mut dat = Dat::default();
+        dat.parse_file(path)?;
+        debug!("{} ROMs loaded from DAT file {}", dat.roms.len(), path);
+        let dat = Rc::new(dat);
+        DAT_CACHE.with(|cache| {
+            cache
+                .borrow_mut()
+                .insert(path.to_string(), (mtime, dat.clone()))
+        });
+
+        Ok(dat)
+    }
+
+    fn parse_file(&mut self, path: &str) -> Result<(), String> {
+        let content = fs::read_to_string(path).map_err(|x| format!("{}", x))?;
+        let document = roxmltree::Document::parse(&content).map_err(|x| format!("{}", x))?;
+
+        for node in document.descendants().filter(|x| x.has_tag_name("rom")) {
+            let game = match node.parent().and_then(|x| x.attribute("name")) {
+                Some(x) => x.to_string(),
+                None => continue,
+            };
+            let hash = |name| node.attribute(name).map(|x: &str| x.to_lowercase());
+            let rom = DatRom {
+                game,
+                size: node.attribute("size").and_then(|x| x.parse().ok()),
+                crc32: hash("crc"),
+                md5: hash("md5"),
+                sha1: hash("sha1"),
+            };
+            let index = self.roms.len();
+
+            if let Some(ref crc32) = rom.crc32 {
+                self.by_crc32.entry(crc32.clone()).or_default().push(index);
+            }
+            if let Some(name) = node.attribute("name") {
+                self.by_name.insert(name.to_string(), index);
+            }
+            self.roms.push(rom);
+        }
+
+        Ok(())
+    }
+
+    fn find_hashes(&self, hashes: &Hashes) -> Option<&DatRom> {
+        self.by_crc32
+            .get(&hashes.crc32)
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.roms[i])
+            .find(|x| x.matches(hashes))
+    }
+
+    fn find_name(&self, file_name: &str) -> Option<&DatRom> {
+        self.by_name.get(file_name).map(|&i| &self.roms[i])
+    }
+}
+
+/// Find the status of a file in the DAT files and the canonical name of its game
+pub fn verify(dats: &[Rc<Dat>], file_name: &str, hashes: &Hashes) -> (RomStatus, Option<String>) {
+    if let Some(rom) = dats.iter().find_map(|x| x.find_hashes(hashes)) {
+        return (RomStatus::Verified, Some(rom.game.clone()));
+    }
+
+    match dats.iter().find_map(|x| x.find_name(file_name)) {
+        Some(rom) => (RomStatus::Bad, Some(rom.game.clone())),
+        None => (RomStatus::Unknown, None),
+    }
+}
+
+/// Modification time of a file, in seconds
+fn modified(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |x| x.as_secs())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CachedHashes {
+    size: u64,
+    mtime: u64,
+    hashes: Hashes,
+}
+
+/// Hashes of the files already verified, kept as long as their size and modification time don't
+/// change
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct HashCache {
+    files: HashMap<String, CachedHashes>,
+    #[serde(skip)]
+    modified: bool,
+}
+
+impl HashCache {
+    pub fn load() -> HashCache {
+        match File::open(HASH_CACHE_FILE) {
+            Ok(file) => serde_json::from_reader(file).unwrap_or_else(|err| {
+                warn!("could not load hash cache: {}", err);
+                HashCache::default()
+            }),
+            Err(_) => HashCache::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if !self.modified {
+            return Ok(());
+        }
+
+        let serialized = serde_json::to_string(self).map_err(|x| format!("{}", x))?;
+        let mut file = File::create(HASH_CACHE_FILE).map_err(|x| x.to_string())?;
+
+        file.write_all(serialized.as_bytes())
+            .map_err(|x| format!("{}", x))
+    }
+
+    /// Hashes of a file (or of a file inside of an archive), computed only if the file changed
+    pub fn get(&mut self, path: &str, archive_entry: Option<&str>) -> Result<Hashes, String> {
+        let metadata = fs::metadata(path).map_err(|x| format!("{}", x))?;
+        let size = metadata.len();
+        let mtime = modified(&metadata);
+        let key = match archive_entry {
+            Some(entry) => format!("{}#{}", path, entry),
+            None => path.to_string(),
+        };
+
+        if let Some(cached) = self.files.get(&key) {
+            if cached.size == size && cached.mtime == mtime {
+                return Ok(cached.hashes.clone());
+            }
+        }
+
+        debug!("hashing {}", key);
+        let hashes = match archive_entry {
+            Some(entry) => archive::read_file(Path::new(path), entry, Hashes::compute)?,
+            None => File::open(path)
+                .and_then(|x| Hashes::compute(&mut io::BufReader::new(x)))
+                .map_err(|x| format!("{}", x))?,
+        };
+        self.files.insert(
+            key,
+            CachedHashes {
+                size,
+                mtime,
+                hashes: hashes.clone(),
+            },
+        );
+        self.modified = true;
+
+        Ok(hashes)
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DISC_TAGS: [&str; 3] = ["(disc ", "(disk ", "(cd"];
+
+pub fn is_playlist(path: &Path) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .map(|x| x.eq_ignore_ascii_case("m3u"))
+        == Some(true)
+}
+
+/// Read the files referenced by a m3u playlist (relative paths are resolved from the playlist's
+/// folder)
+pub fn read_playlist(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let content = fs::read_to_string(path).map_err(|x| format!("{}", x))?;
+
+    Ok(content
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .map(|x| dir.join(x))
+        .collect())
+}
+
+/// Split a name like "Snatcher (Japan) (Disc 2)" into the name of the game without the disc tag
+/// ("Snatcher (Japan)") and the disc number (2)
+pub fn split_disc(name: &str) -> Option<(String, u32)> {
+    // NOTE: the ASCII lowercase keeps the offsets of the name
+    let lowercase = name.to_ascii_lowercase();
+
+    for tag in DISC_TAGS.iter() {
+        let start = match lowercase.find(tag) {
+            Some(x) => x,
+            None => continue,
+        };
+        let end = match lowercase[start..].find(')') {
+            Some(x) => start + x + 1,
+            None => continue,
+        };
+        let number = match lowercase[start + tag.len()..end - 1]
+            .trim()
+            .split(' ')
+            .next()
+            .and_then(|x| x.parse().ok())
+        {
+            Some(x) => x,
+            None => continue,
+        };
+        let game = format!("{} {}", name[..start].trim_end(), name[end..].trim_start());
+
+        return Some((game.trim().to_string(), number));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disc_tags() {
+        assert_eq!(
+            split_disc("Snatcher (Japan) (Disc 2)"),
+            Some(("Snatcher (Japan)".to_string(), 2))
+        );
+        assert_eq!(
+            split_disc("Lunar (CD 1) (USA)"),
+            Some(("Lunar (USA)".to_string(), 1))
+        );
+        assert_eq!(
+            split_disc("Myst (disk 3 of 4)"),
+            Some(("Myst".to_string(), 3))
+        );
+        assert_eq!(split_disc("Snatcher (Japan)"), None);
+        assert_eq!(split_disc("Snatcher (Disc A)"), None);
+    }
+
+    #[test]
+    fn non_ascii_name() {
+        // NOTE: "İ" is longer once lowercased with to_lowercase
+        assert_eq!(
+            split_disc("İstanbul Ⱥ (Disc 2) (Europe)"),
+            Some(("İstanbul Ⱥ (Europe)".to_string(), 2))
+        );
+        assert_eq!(split_disc("Ⱥİİİ (CD 10)"), Some(("Ⱥİİİ".to_string(), 10)));
+    }
+}
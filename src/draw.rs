@@ -1,6 +1,7 @@
 use sdl2::rect::Rect;
 use sdl2::render::{Canvas, Texture};
 use sdl2::video::Window;
+use std::collections::{HashMap, VecDeque};
 
 pub struct Font {
     pub texture: Texture,
@@ -80,3 +81,109 @@ impl Font {
         self.y = y + self.line_height;
     }
 }
+
+/// Textures loaded from files. A missing file is remembered as None so it's not looked up again.
+/// The oldest textures are destroyed when the capacity is reached. The file of the image of each
+/// ROM is remembered as well.
+pub struct TextureCache {
+    capacity: usize,
+    textures: HashMap<String, Option<Texture>>,
+    order: VecDeque<String>,
+    /// The image of each ROM, by path of the ROM
+    media_paths: HashMap<String, Option<String>>,
+}
+
+impl TextureCache {
+    pub fn new(capacity: usize) -> TextureCache {
+        TextureCache {
+            capacity,
+            textures: HashMap::new(),
+            order: VecDeque::with_capacity(capacity),
+            media_paths: HashMap::new(),
+        }
+    }
+
+    /// The file of the image of a ROM, looked up only the first time
+    pub fn media_path<F>(&mut self, rom_path: &str, resolve: F) -> Option<String>
+    where
+        F: FnOnce() -> Option<String>,
+    {
+        self.media_paths
+            .entry(rom_path.to_string())
+            .or_insert_with(resolve)
+            .clone()
+    }
+
+    pub fn contains(&self, filepath: &str) -> bool {
+        self.textures.contains_key(filepath)
+    }
+
+    pub fn insert(&mut self, filepath: String, texture: Option<Texture>) {
+        if self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                if let Some(Some(texture)) = self.textures.remove(&oldest) {
+                    unsafe { texture.destroy() };
+                }
+            }
+        }
+
+        self.order.push_back(filepath.clone());
+        self.textures.insert(filepath, texture);
+    }
+
+    pub fn get(&self, filepath: &str) -> Option<&Texture> {
+        self.textures.get(filepath).and_then(|x| x.as_ref())
+    }
+}
+
+/// Cut a text that doesn't fit in the given number of characters
+pub fn ellipsize(text: &str, width: usize) -> String {
+    if text.chars().count() > width {
+        format!("{}...", text.chars().take(width - 3).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+/// Split a text in lines of whole words, the last line is cut when the text doesn't fit
+pub fn wrap(text: &str, width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        let last = format!("{}...", lines[max_lines - 1]);
+        lines[max_lines - 1] = ellipsize(&last, width);
+    }
+
+    lines.into_iter().map(|x| ellipsize(&x, width)).collect()
+}
+
+/// Copy a texture in the middle of an area, scaled to fit without changing its aspect ratio
+#[allow(unused_must_use)]
+pub fn draw_fit(canvas: &mut Canvas<Window>, texture: &Texture, area: Rect) {
+    let query = texture.query();
+    let scale = f32::min(
+        area.width() as f32 / query.width as f32,
+        area.height() as f32 / query.height as f32,
+    );
+    let w = (query.width as f32 * scale) as u32;
+    let h = (query.height as f32 * scale) as u32;
+    let dst = Rect::new(
+        area.x() + (area.width() - w) as i32 / 2,
+        area.y() + (area.height() - h) as i32 / 2,
+        w,
+        h,
+    );
+
+    canvas.copy(texture, None, dst);
+}
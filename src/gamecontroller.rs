@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use crate::store::{AxisState, HatState, JoystickEvent};
+
+const MAPPINGS_FILE: &str = "gamecontrollerdb.txt";
+
+/// The controls of RetroArch and the buttons of a standard pad (in the names of SDL's game
+/// controller mappings) they are on. A sign selects one direction of an axis
+const RETROPAD: [(&str, &str); 24] = [
+    ("b", "a"),
+    ("a", "b"),
+    ("y", "x"),
+    ("x", "y"),
+    ("select", "back"),
+    ("start", "start"),
+    ("up", "dpup"),
+    ("down", "dpdown"),
+    ("left", "dpleft"),
+    ("right", "dpright"),
+    ("l", "leftshoulder"),
+    ("r", "rightshoulder"),
+    ("l2", "lefttrigger"),
+    ("r2", "righttrigger"),
+    ("l3", "leftstick"),
+    ("r3", "rightstick"),
+    ("l_x_plus", "+leftx"),
+    ("l_x_minus", "-leftx"),
+    ("l_y_plus", "+lefty"),
+    ("l_y_minus", "-lefty"),
+    ("r_x_plus", "+rightx"),
+    ("r_x_minus", "-rightx"),
+    ("r_y_plus", "+righty"),
+    ("r_y_minus", "-righty"),
+];
+
+/// Load the mappings of the user (the ones of SDL are always available)
+pub fn load_mappings(subsystem: &sdl2::GameControllerSubsystem) {
+    if !std::path::Path::new(MAPPINGS_FILE).is_file() {
+        return;
+    }
+
+    match subsystem.load_mappings(MAPPINGS_FILE) {
+        Ok(count) => debug!("{} game controller mappings loaded", count),
+        Err(err) => warn!("could not load {}: {}", MAPPINGS_FILE, err),
+    }
+}
+
+/// The events of the controls of RetroArch found in the mapping of a game controller:
+/// "03000000...,Xbox 360 Controller,a:b0,b:b1,dpup:h0.1,leftx:a0,lefttrigger:a2,..."
+pub fn parse_mapping(mapping: &str) -> HashMap<String, JoystickEvent> {
+    let bindings: HashMap<&str, &str> = mapping
+        .split(',')
+        .filter_map(|x| x.split_once(':'))
+        .collect();
+    let mut controls = HashMap::new();
+
+    for &(control, button) in RETROPAD.iter() {
+        let (direction, button) = if let Some(x) = button.strip_prefix('+') {
+            (Some(AxisState::Positive), x)
+        } else if let Some(x) = button.strip_prefix('-') {
+            (Some(AxisState::Negative), x)
+        } else {
+            (None, button)
+        };
+
+        if let Some(event) = bindings
+            .get(button)
+            .and_then(|x| parse_binding(x, direction))
+        {
+            controls.insert(control.to_string(), event);
+        }
+    }
+
+    controls
+}
+
+/// Parse the binding of a button of a mapping: "b3" (button), "h0.4" (hat), "a2", "+a2", "-a1" or
+/// "a1~" (axis, inverted with a tilde)
+fn parse_binding(binding: &str, direction: Option<AxisState>) -> Option<JoystickEvent> {
+    if let Some(x) = binding.strip_prefix('b') {
+        return x.parse().ok().map(JoystickEvent::Button);
+    }
+
+    if let Some((hat, mask)) = binding.strip_prefix('h').and_then(|x| x.split_once('.')) {
+        let state = match mask {
+            "1" => HatState::Up,
+            "2" => HatState::Right,
+            "4" => HatState::Down,
+            "8" => HatState::Left,
+            _ => return None,
+        };
+
+        return hat.parse().ok().map(|x| JoystickEvent::Hat(x, state));
+    }
+
+    let (half, binding) = if let Some(x) = binding.strip_prefix('+') {
+        (Some(AxisState::Positive), x)
+    } else if let Some(x) = binding.strip_prefix('-') {
+        (Some(AxisState::Negative), x)
+    } else {
+        (None, binding)
+    };
+    let (inverted, binding) = match binding.strip_suffix('~') {
+        Some(x) => (true, x),
+        None => (false, binding),
+    };
+    let axis = binding.strip_prefix('a')?.parse().ok()?;
+    let state = match (half, direction, inverted) {
+        (Some(x), _, _) => x,
+        (None, Some(AxisState::Positive), false) | (None, Some(AxisState::Negative), true) => {
+            AxisState::Positive
+        }
+        (None, Some(_), _) => AxisState::Negative,
+        (None, None, _) => AxisState::Positive,
+    };
+
+    Some(JoystickEvent::Axis(axis, state))
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const GAMELIST_FILE: &str = "gamelist.xml";
+
+/// Metadata of a game found in an EmulationStation gamelist.xml
+#[derive(Clone, Debug, Default)]
+pub struct GameInfo {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Release date in the format of EmulationStation: "19910623T000000"
+    pub release_date: Option<String>,
+    pub developer: Option<String>,
+    pub publisher: Option<String>,
+    pub genre: Option<String>,
+    /// Number of players: "1", "1-2", ...
+    pub players: Option<String>,
+    pub image: Option<String>,
+    pub thumbnail: Option<String>,
+}
+
+impl GameInfo {
+    pub fn year(&self) -> Option<u32> {
+        self.release_date
+            .as_ref()
+            .and_then(|x| x.get(..4))
+            .and_then(|x| x.parse().ok())
+    }
+
+    /// One line summary of the game: "1991 - Sonic Team - Sega - Platform - 1 players"
+    pub fn summary(&self) -> String {
+        let year = self.year().map(|x| x.to_string());
+        let publisher = self
+            .publisher
+            .clone()
+            .filter(|x| Some(x) != self.developer.as_ref());
+        let players = self.players.as_ref().map(|x| format!("{} players", x));
+
+        vec![
+            year,
+            self.developer.clone(),
+            publisher,
+            self.genre.clone(),
+            players,
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" - ")
+    }
+}
+
+/// Read the gamelist.xml of a folder if there is one. The games are indexed by the path of their
+/// file
+pub fn load(dir: &Path) -> HashMap<PathBuf, GameInfo> {
+    let path = dir.join(GAMELIST_FILE);
+    let mut games = HashMap::new();
+
+    if !path.is_file() {
+        return games;
+    }
+
+    if let Err(err) = parse_file(&path, dir, &mut games) {
+        warn!("could not load {}: {}", path.display(), err);
+    }
+    debug!("{} games loaded from {}", games.len(), path.display());
+
+    games
+}
+
+fn parse_file(
+    path: &Path,
+    dir: &Path,
+    games: &mut HashMap<PathBuf, GameInfo>,
+) -> Result<(), String> {
+    let content = fs::read_to_string(path).map_err(|x| format!("{}", x))?;
+    let document = roxmltree::Document::parse(&content).map_err(|x| format!("{}", x))?;
+
+    for node in document.descendants().filter(|x| x.has_tag_name("game")) {
+        let text = |name| {
+            node.children()
+                .find(|x| x.has_tag_name(name))
+                .and_then(|x| x.text())
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+        };
+        let resolve = |name| text(name).map(|x| dir.join(x).to_str().unwrap().to_string());
+        let game_path = match text("path") {
+            Some(x) => dir.join(x),
+            None => continue,
+        };
+
+        games.insert(
+            game_path,
+            GameInfo {
+                name: text("name"),
+                description: text("desc"),
+                release_date: text("releasedate"),
+                developer: text("developer"),
+                publisher: text("publisher"),
+                genre: text("genre"),
+                players: text("players"),
+                image: resolve("image"),
+                thumbnail: resolve("thumbnail"),
+            },
+        );
+    }
+
+    Ok(())
+}
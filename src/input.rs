@@ -0,0 +1,168 @@
+use sdl2::event::Event;
+use sdl2::joystick::HatState;
+
+use crate::keyboard::{is_text_key, KEYBOARD_ID};
+use crate::store::{Action, Store};
+
+pub const AXIS_THRESOLD: i16 = 0x4fff;
+pub const JOYSTICK_LOCK_TIME: u32 = 200; // TODO: longer lock?
+pub const JOYSTICK_LOCK_TIME_AXIS: u32 = 400; // TODO: longer lock?
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuInputKind {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// The button is pressed (it is confirmed once released)
+    Press,
+    Confirm,
+    Back,
+}
+
+/// An input of the menus, whatever the device it comes from. The device is the instance id of a
+/// joystick (or the keyboard) and the split is the part of the joystick used by a player
+#[derive(Clone, Copy, Debug)]
+pub struct MenuInput {
+    pub device: i32,
+    pub split: u32,
+    pub kind: MenuInputKind,
+    pub timestamp: u32,
+}
+
+/// Translate a raw event of SDL to an input of the menus. The directions are locked for a moment
+/// per device so holding them doesn't scroll too fast
+pub fn translate_event(event: &Event, store: &mut Store) -> Option<MenuInput> {
+    use self::MenuInputKind::*;
+
+    let (device, split, kind, timestamp, lock_time) = match *event {
+        Event::JoyHatMotion {
+            which,
+            hat_idx,
+            state,
+            timestamp,
+        } => {
+            let kind = match state {
+                HatState::Up => Up,
+                HatState::Down => Down,
+                HatState::Left => Left,
+                HatState::Right => Right,
+                _ => return None,
+            };
+
+            (which, hat_idx as u32, kind, timestamp, JOYSTICK_LOCK_TIME)
+        }
+        Event::JoyAxisMotion {
+            which,
+            axis_idx,
+            value,
+            timestamp,
+        } if value <= -AXIS_THRESOLD || value >= AXIS_THRESOLD => {
+            let kind = match (axis_idx % 2 == 1, value.is_positive()) {
+                (true, false) => Up,
+                (true, true) => Down,
+                (false, false) => Left,
+                (false, true) => Right,
+            };
+
+            (
+                which,
+                axis_idx as u32 / 2,
+                kind,
+                timestamp,
+                JOYSTICK_LOCK_TIME_AXIS,
+            )
+        }
+        Event::JoyButtonDown {
+            which,
+            button_idx,
+            timestamp,
+        } => {
+            let split_value = split_value(store, which)?;
+            if !(button_idx as u32).is_multiple_of(split_value) {
+                return None;
+            }
+
+            (which, button_idx as u32 / split_value, Press, timestamp, 0)
+        }
+        Event::JoyButtonUp {
+            which,
+            button_idx,
+            timestamp,
+        } => {
+            let split_value = split_value(store, which)?;
+            let split = button_idx as u32 / split_value;
+            // NOTE: the second button may be bound to a control of a player
+            let kind = match button_idx as u32 % split_value {
+                0 => Confirm,
+                1 if !store.get_state().is_grabbing(which, split) => Back,
+                _ => return None,
+            };
+
+            (which, split, kind, timestamp, 0)
+        }
+        // NOTE: the keys are bound to the controls or typed in the search
+        Event::KeyDown {
+            keycode: Some(keycode),
+            ..
+        }
+        | Event::KeyUp {
+            keycode: Some(keycode),
+            ..
+        } if store.get_state().keyboard_is_grabbing()
+            || (store.get_state().is_typing_search() && is_text_key(keycode)) =>
+        {
+            return None
+        }
+        Event::KeyDown {
+            keycode: Some(keycode),
+            repeat,
+            timestamp,
+            ..
+        } => match store.get_state().key_map.input_kind(keycode)? {
+            Confirm if !repeat => (KEYBOARD_ID, 0, Press, timestamp, 0),
+            Press | Confirm | Back => return None,
+            kind => (KEYBOARD_ID, 0, kind, timestamp, JOYSTICK_LOCK_TIME),
+        },
+        Event::KeyUp {
+            keycode: Some(keycode),
+            timestamp,
+            ..
+        } => match store.get_state().key_map.input_kind(keycode)? {
+            kind @ Confirm | kind @ Back => (KEYBOARD_ID, 0, kind, timestamp, 0),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    if lock_time > 0 {
+        let last_action = store
+            .get_state()
+            .last_joystick_action
+            .get(&(device, split))
+            .cloned()
+            .unwrap_or(0);
+        if timestamp < last_action + lock_time {
+            return None;
+        }
+
+        store.dispatch(Action::UpdateJoystickLastAction(timestamp, device, split));
+    }
+
+    Some(MenuInput {
+        device,
+        split,
+        kind,
+        timestamp,
+    })
+}
+
+/// Number of buttons of each part of a joystick
+pub fn split_value(store: &Store, joystick_id: i32) -> Option<u32> {
+    store
+        .get_state()
+        .joysticks
+        .get(&joystick_id)
+        .map(|x| x.buttons / x.split)
+        .filter(|&x| x > 0)
+}
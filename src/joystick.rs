@@ -28,7 +28,7 @@ pub struct JoystickInfo {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-pub struct JoystickGuid([u8; 16]);
+pub struct JoystickGuid(pub [u8; 16]);
 
 impl Serialize for JoystickGuid {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -81,6 +81,26 @@ impl<'de> Visitor<'de> for JoystickGuidVisitor {
     }
 }
 
+impl JoystickGuid {
+    /// The USB vendor and product ids of the joystick when SDL found them (they are stored in
+    /// little endian at the bytes 4 and 8 of the GUID)
+    pub fn vendor_product(&self) -> Option<(u16, u16)> {
+        let bytes = &self.0;
+
+        if bytes[6..8] != [0, 0] || bytes[10..12] != [0, 0] {
+            return None;
+        }
+
+        let vendor = u16::from_le_bytes([bytes[4], bytes[5]]);
+        let product = u16::from_le_bytes([bytes[8], bytes[9]]);
+        if vendor == 0 && product == 0 {
+            None
+        } else {
+            Some((vendor, product))
+        }
+    }
+}
+
 impl JoystickInfo {
     pub fn new(joystick: &Joystick, index: usize) -> JoystickInfo {
         let instance_id = joystick.instance_id();
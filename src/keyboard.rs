@@ -0,0 +1,136 @@
+use sdl2::keyboard::Keycode;
+
+use crate::input::MenuInputKind;
+use crate::joystick::{JoystickGuid, JoystickInfo};
+
+/// Instance id of the keyboard when it is used as a joystick
+pub const KEYBOARD_ID: i32 = -1;
+
+/// Keys used to navigate in the menus, by their SDL names ("Up", "Return", "W", ...)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct KeyMap {
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub confirm: Vec<String>,
+    pub back: Vec<String>,
+}
+
+impl Default for KeyMap {
+    fn default() -> KeyMap {
+        KeyMap {
+            up: vec!["Up".to_string()],
+            down: vec!["Down".to_string()],
+            left: vec!["Left".to_string()],
+            right: vec!["Right".to_string()],
+            confirm: vec!["Return".to_string()],
+            back: vec!["Backspace".to_string()],
+        }
+    }
+}
+
+impl KeyMap {
+    pub fn input_kind(&self, keycode: Keycode) -> Option<MenuInputKind> {
+        let matches = |keys: &[String]| keys.iter().any(|x| Keycode::from_name(x) == Some(keycode));
+
+        if matches(&self.up) {
+            Some(MenuInputKind::Up)
+        } else if matches(&self.down) {
+            Some(MenuInputKind::Down)
+        } else if matches(&self.left) {
+            Some(MenuInputKind::Left)
+        } else if matches(&self.right) {
+            Some(MenuInputKind::Right)
+        } else if matches(&self.confirm) {
+            Some(MenuInputKind::Confirm)
+        } else if matches(&self.back) {
+            Some(MenuInputKind::Back)
+        } else {
+            None
+        }
+    }
+}
+
+/// The keyboard seen as a joystick with one hat and one button
+pub fn keyboard_info() -> JoystickInfo {
+    let mut guid = [0; 16];
+    guid[..8].copy_from_slice(b"keyboard");
+
+    JoystickInfo {
+        instance_id: KEYBOARD_ID,
+        guid: JoystickGuid(guid),
+        index: 0,
+        split: 1,
+        buttons: 1,
+    }
+}
+
+/// The key types a character: a letter, a digit, a sign or the space
+pub fn is_text_key(keycode: Keycode) -> bool {
+    keycode == Keycode::Space || keycode.name().chars().count() == 1
+}
+
+/// Name of a key in the configuration of RetroArch
+pub fn retroarch_key(keycode: Keycode) -> Option<String> {
+    let name = keycode.name();
+    let mut chars = name.chars();
+
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphabetic() {
+            return Some(c.to_ascii_lowercase().to_string());
+        } else if c.is_ascii_digit() {
+            return Some(format!("num{}", c));
+        }
+    }
+
+    let key = match keycode {
+        Keycode::Up => "up",
+        Keycode::Down => "down",
+        Keycode::Left => "left",
+        Keycode::Right => "right",
+        Keycode::Return => "enter",
+        Keycode::Space => "space",
+        Keycode::Tab => "tab",
+        Keycode::Backspace => "backspace",
+        Keycode::LShift => "shift",
+        Keycode::RShift => "rshift",
+        Keycode::LCtrl => "ctrl",
+        Keycode::RCtrl => "rctrl",
+        Keycode::LAlt => "alt",
+        Keycode::RAlt => "ralt",
+        Keycode::Insert => "insert",
+        Keycode::Delete => "del",
+        Keycode::Home => "home",
+        Keycode::End => "end",
+        Keycode::PageUp => "pageup",
+        Keycode::PageDown => "pagedown",
+        Keycode::Comma => "comma",
+        Keycode::Period => "period",
+        Keycode::Slash => "slash",
+        Keycode::Semicolon => "semicolon",
+        Keycode::Minus => "minus",
+        Keycode::Equals => "equals",
+        Keycode::LeftBracket => "leftbracket",
+        Keycode::RightBracket => "rightbracket",
+        Keycode::Backslash => "backslash",
+        Keycode::Quote => "quote",
+        Keycode::Backquote => "backquote",
+        Keycode::F1 => "f1",
+        Keycode::F2 => "f2",
+        Keycode::F3 => "f3",
+        Keycode::F4 => "f4",
+        Keycode::F5 => "f5",
+        Keycode::F6 => "f6",
+        Keycode::F7 => "f7",
+        Keycode::F8 => "f8",
+        Keycode::F9 => "f9",
+        Keycode::F10 => "f10",
+        Keycode::F11 => "f11",
+        Keycode::F12 => "f12",
+        _ => return None,
+    };
+
+    Some(key.to_string())
+}
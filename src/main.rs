@@ -1,7 +1,10 @@
+extern crate crc32fast;
 extern crate env_logger;
 extern crate id_tree;
 #[macro_use]
 extern crate log;
+extern crate md5;
+extern crate roxmltree;
 extern crate sdl2;
 extern crate serde;
 #[macro_use]
@@ -9,23 +12,38 @@ extern crate serde_derive;
 extern crate dirs;
 extern crate num;
 extern crate serde_json;
+extern crate sevenz_rust;
+extern crate sha1_smol;
 extern crate tempfile;
+extern crate zip;
 
-use env_logger::Builder;
+use env_logger::Builder as LoggerBuilder;
 use log::LevelFilter;
-use std::process::Command;
-use tempfile::NamedTempFile;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tempfile::Builder;
 
 mod app;
+mod archive;
+mod autoconfig;
+mod backend;
+mod command;
+mod dat;
+mod disc;
 mod draw;
+mod gamecontroller;
+mod gamelist;
+mod input;
 mod joystick;
+mod keyboard;
+mod process;
 mod rom_launcher;
+mod romname;
 mod store;
 mod tearing;
 
 #[cfg(debug_assertions)]
 fn initialize_logger() {
-    let mut builder = Builder::new();
+    let mut builder = LoggerBuilder::new();
 
     builder.filter_level(LevelFilter::Debug);
     builder.format_timestamp_nanos();
@@ -35,7 +53,7 @@ fn initialize_logger() {
 
 #[cfg(not(debug_assertions))]
 fn initialize_logger() {
-    let mut builder = Builder::new();
+    let mut builder = LoggerBuilder::new();
 
     builder.filter_level(LevelFilter::Info);
     builder.format_timestamp_nanos();
@@ -71,27 +89,84 @@ pub fn main() {
     info!("starting up");
 
     let mut command;
+    let mut session = None;
+    let mut error = None;
+    let mut launcher: Option<rom_launcher::ROMLauncher> = None;
     loop {
         {
-            let app = initialize_app();
-            let mut romlauncher = rom_launcher::ROMLauncher::new(app);
+            let mut romlauncher = match launcher.take() {
+                Some(mut romlauncher) => {
+                    romlauncher.resume(session.take(), error.take());
+                    romlauncher
+                }
+                None => {
+                    rom_launcher::ROMLauncher::new(initialize_app(), session.take(), error.take())
+                }
+            };
             command = romlauncher.run_loop();
+
+            // NOTE: otherwise the launcher is dropped, releasing SDL, before the game is launched
+            if command.is_some() && romlauncher.keep_alive() {
+                romlauncher.suspend();
+                launcher = Some(romlauncher);
+            }
         }
 
         match command {
-            Some((command, config, rom)) => {
+            Some(launch) => {
                 use std::io::Write;
 
-                let mut file = NamedTempFile::new().expect("can't open temporary file");
-                write!(file, "{}", &config).unwrap();
+                let mut file = Builder::new()
+                    .suffix(".cfg")
+                    .tempfile()
+                    .expect("can't open temporary file");
+                write!(file, "{}", &launch.config).unwrap();
+
+                let playlist = launch.playlist.as_ref().map(|discs| {
+                    let mut playlist = Builder::new()
+                        .suffix(".m3u")
+                        .tempfile_in(file.path().parent().unwrap())
+                        .expect("can't open temporary playlist");
+                    for disc in discs {
+                        writeln!(playlist, "{}", disc).unwrap();
+                    }
+                    playlist
+                });
+                let playlist_path = playlist.as_ref().map(|x| x.path().to_str().unwrap());
+                let rom = playlist_path.unwrap_or(&launch.rom);
 
-                let status = Command::new(command.get(0).unwrap())
-                    .args(command.iter().skip(1))
-                    .args(&["--appendconfig", file.path().to_str().unwrap(), &rom])
-                    .status()
-                    .expect("retroarch failed to start");
+                let started = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |x| x.as_secs());
+                let instant = Instant::now();
+                // NOTE: the placeholders have been checked before the game was launched
+                let config_path = file.path().to_str().unwrap();
+                let default_args = launch.backend.get().args(config_path, &launch.config, rom);
+                let command = command::build(
+                    &launch.command,
+                    &launch.rom,
+                    playlist_path,
+                    config_path,
+                    launch.player_count,
+                    &launch.emulator_id,
+                    default_args,
+                )
+                .unwrap();
 
-                info!("retroarch exited with code {}", status);
+                match process::run(&command, &launch.env) {
+                    Ok(()) => {
+                        session = Some(store::PlaySession {
+                            emulator_id: launch.emulator_id,
+                            rom_key: launch.rom_key,
+                            started,
+                            duration: instant.elapsed().as_secs(),
+                        });
+                    }
+                    Err(message) => {
+                        warn!("the game could not be launched: {}", message);
+                        error = Some(message);
+                    }
+                }
             }
             None => {
                 debug!("no command received");
@@ -0,0 +1,83 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::draw::ellipsize;
+
+/// Number of lines of the output of the emulator kept for the error screen
+const OUTPUT_LINES: usize = 10;
+/// Width of the lines of the error screen
+const LINE_WIDTH: usize = 42;
+/// An emulator exiting with an error before this time failed to run the game
+const QUICK_EXIT: Duration = Duration::from_secs(10);
+/// Time given to the output of a failed emulator to be read (the pipes may be kept open by other
+/// processes it started)
+const OUTPUT_TIMEOUT: Duration = Duration::from_secs(1);
+
+type Output = Arc<Mutex<VecDeque<String>>>;
+
+/// Keep the last lines written by the emulator, tell when the output is closed
+fn capture<R: Read + Send + 'static>(reader: R, output: Output, done: Sender<()>) {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            debug!("emulator: {}", line);
+
+            let mut output = output.lock().unwrap();
+            if output.len() == OUTPUT_LINES {
+                output.pop_front();
+            }
+            output.push_back(line);
+        }
+        let _ = done.send(());
+    });
+}
+
+/// Run the emulator and wait for it. The message of the error screen is returned when it can't
+/// start or when it exits with an error shortly after it started
+pub fn run(command: &[String], env: &[(String, String)]) -> Result<(), String> {
+    let command_line = ellipsize(&command.join(" "), 2 * LINE_WIDTH);
+    let program = match command.first() {
+        Some(program) => program,
+        None => return Err("The command of the emulator is empty".to_string()),
+    };
+    let started = Instant::now();
+    let mut child = Command::new(program)
+        .args(&command[1..])
+        .envs(env.iter().cloned())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|x| format!("Could not start the emulator: {}\n\n{}", x, command_line))?;
+
+    let output = Arc::new(Mutex::new(VecDeque::with_capacity(OUTPUT_LINES)));
+    let (done, closed) = mpsc::channel();
+    capture(child.stdout.take().unwrap(), output.clone(), done.clone());
+    capture(child.stderr.take().unwrap(), output.clone(), done);
+    let status = child.wait().map_err(|x| format!("{}", x))?;
+
+    info!("the emulator exited with code {}", status);
+    if status.success() || started.elapsed() >= QUICK_EXIT {
+        return Ok(());
+    }
+
+    // NOTE: the readers are left behind when the output is not closed in time
+    let deadline = Instant::now() + OUTPUT_TIMEOUT;
+    for _ in 0..2 {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if closed.recv_timeout(timeout).is_err() {
+            break;
+        }
+    }
+
+    let mut message = format!("The emulator failed ({})\n\n{}\n\n", status, command_line);
+    for line in output.lock().unwrap().iter() {
+        message.push_str(&ellipsize(line, LINE_WIDTH));
+        message.push('\n');
+    }
+
+    Err(message)
+}
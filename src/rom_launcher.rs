@@ -7,23 +7,31 @@ use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 use std::cmp;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::path::Path;
 
 use crate::app::*;
+use crate::autoconfig;
+use crate::backend;
+use crate::backend::PlayerControls;
+use crate::dat::RomStatus;
 use crate::draw::*;
+use crate::gamecontroller;
+use crate::input;
+use crate::input::*;
+use crate::keyboard::{keyboard_info, retroarch_key, KEYBOARD_ID};
 use crate::store;
 use crate::store::*;
 use crate::tearing::*;
 
-const ENTITES: usize = 23;
+const ENTITES: usize = 33;
 const TV_XRES: i32 = 256;
 const TV_YRES: i32 = 224;
-const AXIS_THRESOLD: i16 = 0x4fff;
-const JOYSTICK_LOCK_TIME: u32 = 200; // TODO: longer lock?
-const JOYSTICK_LOCK_TIME_AXIS: u32 = 400; // TODO: longer lock?
+const LONG_PRESS_TIME: u32 = 700;
+const PREVIEW_AREA: (i32, i32, u32, u32) = (136, 22, 120, 165);
 
 macro_rules! set_highlight {
     ($canvas:expr, $font:expr, $value:expr, $text:expr) => {
@@ -77,33 +85,11 @@ macro_rules! lock_joystick_axis {
     };
 }
 
-macro_rules! translate_to_retroarch_button {
-    ($event:expr) => {
-        match *$event {
-            Button(x) => format!("btn = {}", x),
-            Hat(x, ref state) => format!(
-                "btn = h{}{}",
-                x,
-                match *state {
-                    HatState::Up => "up",
-                    HatState::Down => "down",
-                    HatState::Left => "left",
-                    HatState::Right => "right",
-                }
-            ),
-            Axis(x, ref state) => match *state {
-                AxisState::Positive => format!("axis = +{}", x),
-                AxisState::Negative => format!("axis = -{}", x),
-            },
-            Unassigned => panic!(),
-        }
-    };
-}
-
 pub trait Entity {
     fn is_active(&self, _state: &State) -> bool;
     fn render(&self, canvas: &mut Canvas<Window>, state: &State, resources: &mut Resources);
-    fn apply_event(&self, event: &Event, app: &mut App, store: &mut Store);
+    fn apply_event(&self, _event: &Event, _app: &mut App, _store: &mut Store) {}
+    fn apply_input(&self, _input: &MenuInput, _app: &mut App, _store: &mut Store) {}
 }
 
 struct List {}
@@ -123,11 +109,25 @@ impl Entity for List {
         } else {
             resources.font.texture.set_color_mod(255, 255, 255);
         }
+        let title = if let Some(ref search) = state.search {
+            format!("Search: {}_", search)
+        } else if state.folder.is_empty() {
+            state.get_system_name().to_string()
+        } else {
+            format!("{}/{}", state.get_system_name(), state.folder)
+        };
+        let title = match state.sort_mode {
+            SortMode::Name => title,
+            sort_mode => format!("{} [{}]", title, sort_mode.name()),
+        };
         resources
             .font
-            .println(canvas, &format!("< {: ^38} >", state.get_emulator().name));
+            .println(canvas, &format!("< {: ^38} >", ellipsize(&title, 38)));
         resources.font.println(canvas, "");
 
+        let has_preview = state.has_preview();
+        let name_width = if has_preview { 20 } else { 40 };
+
         resources.font.texture.set_color_mod(255, 255, 255);
         match state.roms {
             Ok(ref roms) => {
@@ -139,23 +139,31 @@ impl Entity for List {
                 {
                     if i as i32 == state.rom_selected {
                         resources.font.texture.set_color_mod(255, 255, 0);
+                    } else if rom.status == RomStatus::Bad {
+                        resources.font.texture.set_color_mod(255, 0, 0);
                     }
-                    if rom.name.len() > 39 {
-                        resources
-                            .font
-                            .println(canvas, &format!("{}...", &rom.name[..39]));
-                    } else {
-                        resources.font.println(canvas, &rom.name);
-                    }
-                    if i as i32 == state.rom_selected {
-                        resources.font.texture.set_color_mod(255, 255, 255);
-                    }
+                    let name = match rom.kind {
+                        RomKind::Folder(_) if rom.name != ".." => format!("{}/", rom.title),
+                        _ => rom.display_name().to_string(),
+                    };
+                    let marker = if state.is_favorite(rom) { "+ " } else { "  " };
+                    resources.font.println(
+                        canvas,
+                        &format!("{}{}", marker, ellipsize(&name, name_width)),
+                    );
+                    resources.font.texture.set_color_mod(255, 255, 255);
                 }
                 for _ in 0..(PAGE_SIZE - (roms.len() as i32 - state.page_index * PAGE_SIZE)) {
                     resources.font.println(canvas, "");
                 }
 
-                resources.font.println(canvas, "");
+                match state
+                    .get_selected_rom()
+                    .and_then(|x| state.get_play_stats(x))
+                {
+                    Some(stats) => resources.font.println(canvas, &stats.summary()),
+                    None => resources.font.println(canvas, ""),
+                }
                 if state.rom_selected == PAGE_SIZE
                     || (state.page_index == state.page_count - 1
                         && state.rom_selected == state.rom_count % PAGE_SIZE)
@@ -178,6 +186,28 @@ impl Entity for List {
                         )
                     ),
                 );
+
+                if let Some(texture) = state
+                    .get_selected_rom()
+                    .and_then(|x| {
+                        resources
+                            .previews
+                            .media_path(&x.path, || state.get_emulator().media_path(x))
+                    })
+                    .and_then(|x| resources.previews.get(&x))
+                {
+                    let (x, y, w, h) = PREVIEW_AREA;
+                    draw_fit(canvas, texture, Rect::new(x, y, w, h));
+                }
+
+                if let Some(info) = state.get_selected_rom().and_then(|x| x.info.as_ref()) {
+                    resources
+                        .font
+                        .println(canvas, &ellipsize(&info.summary(), 42));
+                    for line in wrap(info.description.as_deref().unwrap_or_default(), 42, 3) {
+                        resources.font.println(canvas, &line);
+                    }
+                }
             }
             Err(ref err) => {
                 resources.font.texture.set_color_mod(255, 0, 0);
@@ -186,183 +216,102 @@ impl Entity for List {
         }
     }
 
-    #[allow(clippy::unnecessary_unwrap)]
-    fn apply_event(&self, event: &Event, mut app: &mut App, store: &mut Store) {
+    fn apply_event(&self, event: &Event, _app: &mut App, store: &mut Store) {
         use store::Action::*;
         let state = store.get_state();
-        let rom_selected = state.rom_selected;
-        let rom_count = state.rom_count;
-        let page_index = state.page_index;
-        let page_count = state.page_count;
 
         match *event {
-            Event::JoyHatMotion {
-                state: HatState::Down,
-                which,
-                hat_idx,
+            Event::TextInput {
+                ref text,
                 timestamp,
                 ..
-            } => {
-                let split_index = hat_idx as u32;
-
-                lock_joystick!(which, split_index, timestamp, store, || store
-                    .dispatch(NextRom { timestamp, step: 1 }))
-            }
-            Event::JoyAxisMotion {
-                axis_idx,
-                value,
-                which,
-                timestamp,
-                ..
-            } if axis_idx % 2 == 1 && value >= AXIS_THRESOLD => {
-                let split_index = axis_idx as u32 / 2;
-
-                lock_joystick_axis!(which, split_index, timestamp, store, || store
-                    .dispatch(NextRom { timestamp, step: 1 }))
-            }
-            Event::JoyHatMotion {
-                state: HatState::Up,
-                which,
-                hat_idx,
+            } => match state.search {
+                Some(ref search) => {
+                    store.dispatch(Search(timestamp, Some(format!("{}{}", search, text))))
+                }
+                None if text == "/" => store.dispatch(Search(timestamp, Some(String::new()))),
+                None => {}
+            },
+            Event::KeyDown {
+                keycode: Some(Keycode::Backspace),
                 timestamp,
                 ..
             } => {
-                let split_index = hat_idx as u32;
-
-                lock_joystick!(which, split_index, timestamp, store, || store.dispatch(
-                    NextRom {
-                        timestamp,
-                        step: -1
-                    }
-                ))
+                if let Some(ref search) = state.search {
+                    let mut search = search.clone();
+                    search.pop();
+                    store.dispatch(Search(timestamp, Some(search)))
+                }
             }
-            Event::JoyAxisMotion {
-                axis_idx,
-                value,
-                which,
+            Event::KeyUp {
+                keycode: Some(Keycode::Escape),
                 timestamp,
                 ..
-            } if axis_idx % 2 == 1 && value <= -AXIS_THRESOLD => {
-                let split_index = axis_idx as u32 / 2;
+            } if state.search.is_some() => store.dispatch(Search(timestamp, None)),
+            _ => {}
+        }
+    }
 
-                lock_joystick_axis!(which, split_index, timestamp, store, || store.dispatch(
-                    NextRom {
-                        timestamp,
-                        step: -1
-                    }
-                ))
+    fn apply_input(&self, input: &MenuInput, mut app: &mut App, store: &mut Store) {
+        use store::Action::*;
+        let state = store.get_state();
+        let rom_selected = state.rom_selected;
+        let rom_count = state.rom_count;
+        let page_index = state.page_index;
+        let page_count = state.page_count;
+        let MenuInput {
+            device,
+            split,
+            timestamp,
+            ..
+        } = *input;
+
+        match input.kind {
+            MenuInputKind::Up => {
+                let action = next_rom_action(state, device, split, timestamp, -1);
+                store.dispatch(action)
             }
-            Event::JoyHatMotion {
-                state: HatState::Right,
-                which,
-                hat_idx,
-                timestamp,
-                ..
-            } => {
-                let split_index = hat_idx as u32;
-
-                if rom_selected == -1 {
-                    lock_joystick!(which, split_index, timestamp, store, || store
-                        .dispatch(NextEmulator { timestamp, step: 1 }))
-                } else {
-                    lock_joystick!(which, split_index, timestamp, store, || store
-                        .dispatch(NextPage { timestamp, step: 1 }))
-                }
+            MenuInputKind::Down => {
+                let action = next_rom_action(state, device, split, timestamp, 1);
+                store.dispatch(action)
             }
-            Event::JoyAxisMotion {
-                axis_idx,
-                value,
-                which,
-                timestamp,
-                ..
-            } if axis_idx % 2 == 0 && value >= AXIS_THRESOLD => {
-                let split_index = axis_idx as u32 / 2;
-
-                if rom_selected == -1 {
-                    lock_joystick!(which, split_index, timestamp, store, || store
-                        .dispatch(NextEmulator { timestamp, step: 1 }))
-                } else {
-                    lock_joystick!(which, split_index, timestamp, store, || store
-                        .dispatch(NextPage { timestamp, step: 1 }))
-                }
+            MenuInputKind::Left => {
+                let action = next_page_action(state, device, split, timestamp, -1);
+                store.dispatch(action)
             }
-            Event::JoyHatMotion {
-                state: HatState::Left,
-                which,
-                hat_idx,
-                timestamp,
-                ..
-            } => {
-                let split_index = hat_idx as u32;
-
-                if rom_selected == -1 {
-                    lock_joystick!(which, split_index, timestamp, store, || store.dispatch(
-                        NextEmulator {
-                            timestamp,
-                            step: -1
-                        }
-                    ))
-                } else {
-                    lock_joystick!(which, split_index, timestamp, store, || store.dispatch(
-                        NextPage {
-                            timestamp,
-                            step: -1
-                        }
-                    ))
-                }
+            MenuInputKind::Right => {
+                let action = next_page_action(state, device, split, timestamp, 1);
+                store.dispatch(action)
             }
-            Event::JoyAxisMotion {
-                axis_idx,
-                value,
-                which,
-                timestamp,
-                ..
-            } if axis_idx % 2 == 0 && value <= -AXIS_THRESOLD => {
-                let split_index = axis_idx as u32 / 2;
-
-                if rom_selected == -1 {
-                    lock_joystick!(which, split_index, timestamp, store, || store.dispatch(
-                        NextEmulator {
-                            timestamp,
-                            step: -1
-                        }
-                    ))
-                } else {
-                    lock_joystick!(which, split_index, timestamp, store, || store.dispatch(
-                        NextPage {
-                            timestamp,
-                            step: -1
-                        }
-                    ))
+            MenuInputKind::Back => {
+                // NOTE: the back key of the keyboard may be used to edit the search
+                if state.search.is_none() && !state.folder.is_empty() {
+                    let folder = state
+                        .folder
+                        .rsplit_once('/')
+                        .map_or("", |x| x.0)
+                        .to_string();
+                    store.dispatch(OpenFolder { timestamp, folder })
                 }
             }
-            Event::JoyButtonUp {
-                button_idx,
-                which,
-                timestamp,
-                ..
-            } => {
-                let split_index = {
-                    let state = store.get_state();
-                    let split_value =
-                        state.joysticks[&which].buttons / state.joysticks[&which].split;
-
-                    if button_idx as u32 % split_value == 0 {
-                        Some(button_idx as u32 / split_value)
-                    } else {
-                        None
-                    }
-                };
-
-                if split_index.is_some() && rom_selected > -1 {
-                    if (page_index < page_count - 1 && rom_selected < PAGE_SIZE)
-                        || (page_index == page_count - 1 && rom_selected < rom_count % PAGE_SIZE)
-                    {
-                        store.dispatch(LaunchGame(timestamp, which, split_index.unwrap()));
-                    } else {
-                        tearing_test(&mut app);
-                        store.dispatch(Rerender(timestamp));
+            MenuInputKind::Confirm if rom_selected > -1 => {
+                let press = state.button_pressed.get(&(device, split)).cloned();
+                let long_press =
+                    press.map(|x| timestamp - x.timestamp >= LONG_PRESS_TIME) == Some(true);
+
+                if press.map(|x| x.combined) == Some(true) {
+                    // NOTE: the button has been used to jump to a letter
+                } else if (page_index < page_count - 1 && rom_selected < PAGE_SIZE)
+                    || (page_index == page_count - 1 && rom_selected < rom_count % PAGE_SIZE)
+                {
+                    match state.get_rom().kind.clone() {
+                        RomKind::Folder(folder) => store.dispatch(OpenFolder { timestamp, folder }),
+                        RomKind::File if long_press => store.dispatch(ToggleFavorite { timestamp }),
+                        RomKind::File => store.dispatch(LaunchGame(timestamp, device, split)),
                     }
+                } else {
+                    tearing_test(&mut app);
+                    store.dispatch(Rerender(timestamp));
                 }
             }
             _ => {}
@@ -394,43 +343,18 @@ impl Entity for GameLauncher {
         }
     }
 
-    fn apply_event(&self, event: &Event, _app: &mut App, store: &mut Store) {
+    fn apply_input(&self, input: &MenuInput, _app: &mut App, store: &mut Store) {
         use store::Action::*;
 
-        #[allow(clippy::single_match)]
-        match *event {
-            Event::JoyButtonUp {
-                which,
-                button_idx,
-                timestamp,
-                ..
-            } => {
-                let split_index = {
-                    let state = store.get_state();
-                    let split_value =
-                        state.joysticks[&which].buttons / state.joysticks[&which].split;
-
-                    if button_idx as u32 % split_value == 0 {
-                        Some(button_idx as u32 / split_value)
-                    } else {
-                        None
-                    }
-                };
-
-                if let Some(split_index) = split_index {
-                    if !store
-                        .get_state()
-                        .players
-                        .iter()
-                        .filter(|x| x.is_some())
-                        .map(|x| x.as_ref().unwrap())
-                        .any(|x| x.joystick == which && x.joystick_split == split_index)
-                    {
-                        store.dispatch(AddPlayer(timestamp, which, split_index));
-                    }
-                }
-            }
-            _ => {}
+        if input.kind == MenuInputKind::Confirm
+            && !store
+                .get_state()
+                .players
+                .iter()
+                .flatten()
+                .any(|x| x.joystick == input.device && x.joystick_split == input.split)
+        {
+            store.dispatch(AddPlayer(input.timestamp, input.device, input.split));
         }
     }
 }
@@ -442,7 +366,10 @@ struct PlayerMenu {
 impl Entity for PlayerMenu {
     fn is_active(&self, state: &State) -> bool {
         if let Some(ref player) = state.players[self.player_index] {
-            if player.grab_input.is_none() && player.grab_emulator_buttons.is_none() {
+            if player.grab_input.is_none()
+                && player.grab_emulator_buttons.is_none()
+                && player.edit_controls.is_none()
+            {
                 return true;
             }
         }
@@ -474,7 +401,7 @@ impl Entity for PlayerMenu {
             .print(canvas, &format!("{:2} ", actual_player_index + 1));
 
         match player.menu {
-            Controls | Ready | Leave => {
+            Controls | Ready | Core | Leave => {
                 set_highlight!(canvas, resources.font, player.menu == Controls, "Controls");
                 if (self.player_index == 0 && !state.all_players_are_ready())
                     || state.player_needs_setup_controls(self.player_index)
@@ -491,8 +418,12 @@ impl Entity for PlayerMenu {
                         "Ready"
                     }
                 );
-                resources.font.print(canvas, "        ");
                 resources.font.texture.set_color_mod(255, 255, 255);
+                if self.player_index == 0 && !state.get_emulator().cores.is_empty() {
+                    set_highlight!(canvas, resources.font, player.menu == Core, "Core");
+                } else {
+                    resources.font.print(canvas, "        ");
+                }
                 set_highlight!(
                     canvas,
                     resources.font,
@@ -525,6 +456,15 @@ impl Entity for PlayerMenu {
                 resources.font.texture.set_color_mod(255, 255, 255);
                 set_highlight!(canvas, resources.font, player.menu == ControlsExit, "Back");
             }
+            CoreChoice => {
+                let name = match state.get_core_name(state.get_rom()) {
+                    "" => "Default",
+                    name => name,
+                };
+                resources
+                    .font
+                    .print(canvas, &format!("Core: < {} >", ellipsize(name, 29)));
+            }
             Waiting => {
                 resources.font.print(canvas, "   ");
                 set_highlight!(canvas, resources.font, true, "Waiting for other players...");
@@ -532,95 +472,61 @@ impl Entity for PlayerMenu {
         }
     }
 
-    fn apply_event(&self, event: &Event, _app: &mut App, store: &mut Store) {
+    fn apply_input(&self, input: &MenuInput, app: &mut App, store: &mut Store) {
         use store::Action::*;
 
         let (player_joystick, player_split) = store.get_state().players[self.player_index]
             .as_ref()
             .map(|x| (x.joystick, x.joystick_split))
             .unwrap();
-        match *event {
-            Event::JoyButtonUp {
-                which,
-                button_idx,
-                timestamp,
-                ..
-            } if player_joystick == which => {
-                let split_index = {
-                    let state = store.get_state();
-                    let split_value =
-                        state.joysticks[&which].buttons / state.joysticks[&which].split;
+        if input.device != player_joystick || input.split != player_split {
+            return;
+        }
 
-                    if button_idx as u32 % split_value == 0 {
-                        Some(button_idx as u32 / split_value)
+        let choosing_core = store.get_state().players[self.player_index]
+            .as_ref()
+            .map(|x| x.menu == store::PlayerMenu::CoreChoice)
+            == Some(true);
+
+        match input.kind {
+            MenuInputKind::Right | MenuInputKind::Left if choosing_core => {
+                store.dispatch(NextCore {
+                    timestamp: input.timestamp,
+                    step: if input.kind == MenuInputKind::Right {
+                        1
                     } else {
-                        None
-                    }
-                };
-
-                if let Some(split_index) = split_index {
-                    if player_split == split_index {
-                        store.dispatch(GoPlayerMenu(timestamp, which, split_index));
-                    }
-                }
+                        -1
+                    },
+                })
             }
-            Event::JoyHatMotion {
-                hat_idx,
-                which,
-                state: HatState::Right,
-                timestamp,
-                ..
-            } => {
-                if player_joystick == which {
-                    let split_index = hat_idx as u32;
-
-                    if player_split == split_index {
-                        store.dispatch(NextPlayerMenu(timestamp, which, split_index));
+            MenuInputKind::Confirm => {
+                let state = store.get_state();
+                let starts = self.player_index == 0
+                    && state.players[0]
+                        .as_ref()
+                        .map(|x| x.menu == store::PlayerMenu::Ready)
+                        == Some(true);
+                let long_press = is_long_press(state, input.device, input.split, input.timestamp);
+                let asks_emulator_buttons = state.asks_emulator_buttons();
+                let has_emulator_buttons = state
+                    .get_emulator_buttons(input.device, input.split)
+                    .is_some();
+
+                // NOTE: a long press on "Start" asks for the emulator buttons again
+                if starts && long_press && asks_emulator_buttons {
+                    store.dispatch(AskEmulatorButtons(input.timestamp));
+                } else {
+                    if starts && (has_emulator_buttons || !asks_emulator_buttons) {
+                        app.quit();
                     }
+                    store.dispatch(GoPlayerMenu(input.timestamp, input.device, input.split));
                 }
             }
-            Event::JoyAxisMotion {
-                axis_idx,
-                value,
-                which,
-                timestamp,
-                ..
-            } if axis_idx % 2 == 0 && value >= AXIS_THRESOLD && player_joystick == which => {
-                let split_index = axis_idx as u32 / 2;
-
-                if player_split == split_index {
-                    lock_joystick!(which, split_index, timestamp, store, || store
-                        .dispatch(NextPlayerMenu(timestamp, which, split_index)))
-                }
-            }
-            Event::JoyHatMotion {
-                hat_idx,
-                which,
-                state: HatState::Left,
-                timestamp,
-                ..
-            } => {
-                if player_joystick == which {
-                    let split_index = hat_idx as u32;
-
-                    if player_split == split_index {
-                        store.dispatch(PrevPlayerMenu(timestamp, which, split_index));
-                    }
-                }
+            MenuInputKind::Right => {
+                store.dispatch(NextPlayerMenu(input.timestamp, input.device, input.split))
             }
-            Event::JoyAxisMotion {
-                axis_idx,
-                value,
-                which,
-                timestamp,
-                ..
-            } if axis_idx % 2 == 0 && value <= -AXIS_THRESOLD && player_joystick == which => {
-                let split_index = axis_idx as u32 / 2;
-
-                if player_split == split_index {
-                    lock_joystick!(which, split_index, timestamp, store, || store
-                        .dispatch(PrevPlayerMenu(timestamp, which, split_index)))
-                }
+            MenuInputKind::Left => {
+                store.dispatch(PrevPlayerMenu(input.timestamp, input.device, input.split))
             }
             _ => {}
         }
@@ -650,25 +556,47 @@ impl Entity for PlayerGrabInput {
             .filter(|x| x.is_some())
             .count();
 
-        resources.font.texture.set_color_mod(255, 255, 255);
+        let player = state.players[self.player_index].as_ref().unwrap();
+        let &(_, ref controls) = player.grab_input.as_ref().unwrap();
+        // NOTE: the input ignored because another control has it is shown in red
+        if player.grab_in_use {
+            resources.font.texture.set_color_mod(255, 0, 0);
+        } else {
+            resources.font.texture.set_color_mod(255, 255, 255);
+        }
         resources.font.set_pos(
             0,
             line_height * self.player_index as i32 + line_height.wrapping_div(4),
         );
-        let &(_, ref controls) = state.players[self.player_index]
-            .as_ref()
-            .unwrap()
-            .grab_input
-            .as_ref()
-            .unwrap();
-        let (_, ref input_display) =
-            state.emulators[state.emulator_selected as usize].controls[controls.len()];
+        let index = match player.edit_controls {
+            Some(ControlEditor {
+                entry: EditorEntry::Control(x),
+                ..
+            }) => x,
+            _ => controls.len(),
+        };
+        let (_, ref input_display) = state.get_emulator().controls[index];
+        // NOTE: a skipped control has no input to press again
+        let back_hint = if player.grab_in_use {
+            "in use"
+        } else if index > 0
+            && index == controls.len()
+            && controls.last() != Some(&JoystickEvent::Unassigned)
+        {
+            "again:back"
+        } else {
+            ""
+        };
+        let remaining =
+            (player.grab_timestamp + GRAB_TIMEOUT).saturating_sub(state.timestamp) + 999;
         resources.font.print(
             canvas,
             &format!(
-                "{:2}   Press button for:   {}",
+                "{:2} {:14} hold:skip {:10} {:>2}s",
                 actual_player_index + 1,
-                input_display
+                ellipsize(input_display, 14),
+                back_hint,
+                remaining / 1000
             ),
         );
     }
@@ -682,23 +610,42 @@ impl Entity for PlayerGrabInput {
             .map(|x| (x.joystick, x.joystick_split))
             .unwrap();
         match *event {
-            Event::JoyButtonUp {
+            Event::JoyButtonDown {
                 which,
                 button_idx,
                 timestamp,
                 ..
             } if player_joystick == which => {
-                let split_index = {
-                    let state = store.get_state();
-                    let split_value =
-                        state.joysticks[&which].buttons / state.joysticks[&which].split;
+                let split_index = match split_value(store, which) {
+                    Some(split_value) => button_idx as u32 / split_value,
+                    None => return,
+                };
 
-                    button_idx as u32 / split_value
+                if player_split == split_index {
+                    store.dispatch(PressButton(timestamp, which, split_index));
+                }
+            }
+            Event::JoyButtonUp {
+                which,
+                button_idx,
+                timestamp,
+                ..
+            } if player_joystick == which => {
+                let split_index = match split_value(store, which) {
+                    Some(split_value) => button_idx as u32 / split_value,
+                    None => return,
                 };
 
                 if player_split == split_index {
+                    // NOTE: a long press skips the control
+                    let event = if is_long_press(store.get_state(), which, split_index, timestamp) {
+                        Unassigned
+                    } else {
+                        Button(button_idx)
+                    };
+                    store.dispatch(ReleaseButton(timestamp, which, split_index));
                     lock_joystick!(which, split_index, timestamp, store, || store.dispatch(
-                        BindPlayerJoystickEvent(timestamp, self.player_index, Button(button_idx))
+                        BindPlayerJoystickEvent(timestamp, self.player_index, event.clone())
                     ))
                 }
             }
@@ -763,14 +710,161 @@ impl Entity for PlayerGrabInput {
                     ))
                 }
             }
-            _ => {}
-        }
-    }
-}
-
-struct PlayerGrabEmulatorButtons;
-
-impl Entity for PlayerGrabEmulatorButtons {
+            Event::KeyDown {
+                keycode: Some(_),
+                repeat: false,
+                timestamp,
+                ..
+            } if player_joystick == KEYBOARD_ID => {
+                store.dispatch(PressButton(timestamp, KEYBOARD_ID, player_split));
+            }
+            Event::KeyUp {
+                keycode: Some(keycode),
+                timestamp,
+                ..
+            } if player_joystick == KEYBOARD_ID => {
+                let long_press =
+                    is_long_press(store.get_state(), KEYBOARD_ID, player_split, timestamp);
+                store.dispatch(ReleaseButton(timestamp, KEYBOARD_ID, player_split));
+                if let Some(key) = retroarch_key(keycode) {
+                    let event = if long_press { Unassigned } else { Key(key) };
+                    lock_joystick!(KEYBOARD_ID, player_split, timestamp, store, || store
+                        .dispatch(BindPlayerJoystickEvent(
+                            timestamp,
+                            self.player_index,
+                            event.clone()
+                        )))
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+struct PlayerControlEditor {
+    player_index: usize,
+}
+
+impl Entity for PlayerControlEditor {
+    fn is_active(&self, state: &State) -> bool {
+        match state.players[self.player_index] {
+            Some(ref player) => player.edit_controls.is_some() && player.grab_input.is_none(),
+            None => false,
+        }
+    }
+
+    #[allow(unused_must_use)]
+    fn render(&self, canvas: &mut Canvas<Window>, state: &State, resources: &mut Resources) {
+        resources.font.set_line_spacing(1.50);
+        let line_height = resources.font.line_height;
+        let actual_player_index = state
+            .players
+            .iter()
+            .take(self.player_index)
+            .filter(|x| x.is_some())
+            .count();
+
+        resources.font.texture.set_color_mod(255, 255, 255);
+        resources.font.set_pos(
+            0,
+            line_height * self.player_index as i32 + line_height.wrapping_div(4),
+        );
+        resources
+            .font
+            .print(canvas, &format!("{:2} ", actual_player_index + 1));
+
+        let editor = state.players[self.player_index]
+            .as_ref()
+            .unwrap()
+            .edit_controls
+            .as_ref()
+            .unwrap();
+        let mapping = state
+            .get_player_controls(self.player_index, editor.target)
+            .unwrap_or_default();
+        match (editor.entry, editor.action) {
+            (EditorEntry::Control(x), Some(action)) => {
+                let (_, ref label) = state.get_controls()[x];
+                resources
+                    .font
+                    .print(canvas, &format!("{:7}", ellipsize(label, 7)));
+                set_highlight!(
+                    canvas,
+                    resources.font,
+                    action == EditorAction::Rebind,
+                    "Rebind"
+                );
+                set_highlight!(
+                    canvas,
+                    resources.font,
+                    action == EditorAction::Unassign,
+                    "Unassign"
+                );
+                set_highlight!(
+                    canvas,
+                    resources.font,
+                    action == EditorAction::Cancel,
+                    "Cancel"
+                );
+            }
+            (entry, _) => {
+                let text = match entry {
+                    EditorEntry::All => "All controls".to_string(),
+                    EditorEntry::Control(x) => {
+                        let (_, ref label) = state.get_controls()[x];
+                        let event = mapping.get(x).unwrap_or(&JoystickEvent::Unassigned);
+
+                        format!("{}: {}", label, event.label())
+                    }
+                    EditorEntry::Exit => "Back".to_string(),
+                };
+                resources
+                    .font
+                    .print(canvas, &format!("< {} >", ellipsize(&text, 35)));
+            }
+        }
+    }
+
+    fn apply_input(&self, input: &MenuInput, _app: &mut App, store: &mut Store) {
+        use store::Action::*;
+
+        let (player_joystick, player_split) = store.get_state().players[self.player_index]
+            .as_ref()
+            .map(|x| (x.joystick, x.joystick_split))
+            .unwrap();
+        if input.device != player_joystick || input.split != player_split {
+            return;
+        }
+
+        let step = match input.kind {
+            MenuInputKind::Confirm => {
+                store.dispatch(GoControlEditor(input.timestamp, input.device, input.split));
+                return;
+            }
+            MenuInputKind::Back => {
+                store.dispatch(BackControlEditor(
+                    input.timestamp,
+                    input.device,
+                    input.split,
+                ));
+                return;
+            }
+            MenuInputKind::Right => 1,
+            MenuInputKind::Left => -1,
+            _ => return,
+        };
+        store.dispatch(NextControlEditor(
+            input.timestamp,
+            input.device,
+            input.split,
+            step,
+        ));
+    }
+}
+
+struct PlayerGrabEmulatorButtons;
+
+impl Entity for PlayerGrabEmulatorButtons {
     fn is_active(&self, state: &State) -> bool {
         state.players[0]
             .as_ref()
@@ -784,12 +878,8 @@ impl Entity for PlayerGrabEmulatorButtons {
         let line_height = resources.font.line_height;
         resources.font.texture.set_color_mod(255, 255, 255);
         resources.font.set_pos(0, line_height.wrapping_div(4));
-        let &(ref hotkey, ref menu) = state.players[0]
-            .as_ref()
-            .unwrap()
-            .grab_emulator_buttons
-            .as_ref()
-            .unwrap();
+        let player = state.players[0].as_ref().unwrap();
+        let &(ref hotkey, ref menu) = player.grab_emulator_buttons.as_ref().unwrap();
         if hotkey.is_none() {
             resources
                 .font
@@ -798,56 +888,242 @@ impl Entity for PlayerGrabEmulatorButtons {
             resources
                 .font
                 .println(canvas, "    Press button for: emulator menu");
+        } else if let Some(ref mapping) = player.grab_hotkeys {
+            if let Some((_, ref label)) = state.get_emulator().hotkeys.get(mapping.len()) {
+                resources.font.println(
+                    canvas,
+                    &format!("  Press button for: {:12} hold:skip", ellipsize(label, 12)),
+                );
+            }
         }
     }
 
     fn apply_event(&self, event: &Event, app: &mut App, store: &mut Store) {
         use self::JoystickEvent::*;
-        use store::Action::*;
 
         let (player_joystick, player_split) = store.get_state().players[0]
             .as_ref()
             .map(|x| (x.joystick, x.joystick_split))
             .unwrap();
         match *event {
-            Event::JoyButtonUp {
+            Event::JoyButtonDown {
                 which,
                 button_idx,
                 timestamp,
                 ..
             } if player_joystick == which => {
-                let split_index = {
-                    let state = store.get_state();
-                    let split_value =
-                        state.joysticks[&which].buttons / state.joysticks[&which].split;
+                let split_index = match split_value(store, which) {
+                    Some(split_value) => button_idx as u32 / split_value,
+                    None => return,
+                };
 
-                    button_idx as u32 / split_value
+                if player_split == split_index {
+                    store.dispatch(Action::PressButton(timestamp, which, split_index));
+                }
+            }
+            Event::JoyButtonUp {
+                which,
+                button_idx,
+                timestamp,
+                ..
+            } if player_joystick == which => {
+                let split_index = match split_value(store, which) {
+                    Some(split_value) => button_idx as u32 / split_value,
+                    None => return,
                 };
-                let hotkey = store.get_state().players[0]
-                    .as_ref()
-                    .unwrap()
-                    .grab_emulator_buttons
-                    .as_ref()
-                    .unwrap()
-                    .0
-                    .clone();
 
                 if player_split == split_index {
-                    lock_joystick!(which, split_index, timestamp, store, || {
-                        let new_joystick_event = Button(button_idx);
+                    // NOTE: a long press skips the hotkey
+                    let event = if is_long_press(store.get_state(), which, split_index, timestamp) {
+                        Unassigned
+                    } else {
+                        Button(button_idx)
+                    };
+                    store.dispatch(Action::ReleaseButton(timestamp, which, split_index));
+                    lock_joystick!(which, split_index, timestamp, store, || self.bind(
+                        timestamp,
+                        event.clone(),
+                        app,
+                        store
+                    ))
+                }
+            }
+            Event::JoyHatMotion {
+                which,
+                hat_idx,
+                state,
+                timestamp,
+                ..
+            } if player_joystick == which && player_split == hat_idx as u32 => {
+                let state = match state {
+                    HatState::Up => store::HatState::Up,
+                    HatState::Down => store::HatState::Down,
+                    HatState::Left => store::HatState::Left,
+                    HatState::Right => store::HatState::Right,
+                    _ => return,
+                };
 
-                        if let Some(joystick_event) = hotkey {
-                            if joystick_event == new_joystick_event {
-                                return;
-                            }
+                lock_joystick!(which, player_split, timestamp, store, || self.bind(
+                    timestamp,
+                    Hat(hat_idx, state.clone()),
+                    app,
+                    store
+                ))
+            }
+            Event::JoyAxisMotion {
+                which,
+                axis_idx,
+                value,
+                timestamp,
+                ..
+            } if player_joystick == which
+                && player_split == axis_idx as u32 / 2
+                && (value <= -AXIS_THRESOLD || value >= AXIS_THRESOLD) =>
+            {
+                let state = if value.is_positive() {
+                    AxisState::Positive
+                } else {
+                    AxisState::Negative
+                };
 
-                            app.quit();
-                        }
+                lock_joystick_axis!(which, player_split, timestamp, store, || self.bind(
+                    timestamp,
+                    Axis(axis_idx, state.clone()),
+                    app,
+                    store
+                ))
+            }
+            Event::KeyDown {
+                keycode: Some(_),
+                repeat: false,
+                timestamp,
+                ..
+            } if player_joystick == KEYBOARD_ID => {
+                store.dispatch(Action::PressButton(timestamp, KEYBOARD_ID, player_split));
+            }
+            Event::KeyUp {
+                keycode: Some(keycode),
+                timestamp,
+                ..
+            } if player_joystick == KEYBOARD_ID => {
+                let long_press =
+                    is_long_press(store.get_state(), KEYBOARD_ID, player_split, timestamp);
+                store.dispatch(Action::ReleaseButton(timestamp, KEYBOARD_ID, player_split));
+                if let Some(key) = retroarch_key(keycode) {
+                    let event = if long_press { Unassigned } else { Key(key) };
+                    lock_joystick!(KEYBOARD_ID, player_split, timestamp, store, || self.bind(
+                        timestamp,
+                        event.clone(),
+                        app,
+                        store
+                    ))
+                }
+            }
+            _ => {}
+        }
+    }
+}
 
-                        store.dispatch(BindEmulatorButton(timestamp, new_joystick_event));
-                    })
+impl PlayerGrabEmulatorButtons {
+    /// Bind the hotkey button, the menu button and the hotkeys of the emulator, then start the game
+    fn bind(&self, timestamp: u32, event: JoystickEvent, app: &mut App, store: &mut Store) {
+        let state = store.get_state();
+        let player = state.players[0].as_ref().unwrap();
+        let (hotkey, menu) = player.grab_emulator_buttons.clone().unwrap();
+        let hotkeys_len = state.get_emulator().hotkeys.len();
+
+        match (hotkey, menu, player.grab_hotkeys.as_ref()) {
+            (None, _, _) if event != JoystickEvent::Unassigned => {
+                store.dispatch(Action::BindEmulatorButton(timestamp, event))
+            }
+            (Some(hotkey), None, _) if event != JoystickEvent::Unassigned && event != hotkey => {
+                if hotkeys_len == 0 {
+                    app.quit();
                 }
+                store.dispatch(Action::BindEmulatorButton(timestamp, event));
+            }
+            (Some(_), Some(_), Some(mapping)) if mapping.len() < hotkeys_len => {
+                if mapping.len() + 1 == hotkeys_len {
+                    app.quit();
+                }
+                store.dispatch(Action::BindEmulatorHotkey(timestamp, event));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The button of the joystick has been held long enough to be a long press
+fn is_long_press(state: &State, joystick: i32, split: u32, timestamp: u32) -> bool {
+    state
+        .button_pressed
+        .get(&(joystick, split))
+        .map(|x| timestamp - x.timestamp >= LONG_PRESS_TIME)
+        == Some(true)
+}
+
+/// Move the selection of the list or, while the button of the joystick is held, jump to the next
+/// letter
+fn next_rom_action(state: &State, joystick: i32, split: u32, timestamp: u32, step: i32) -> Action {
+    if state.button_pressed.contains_key(&(joystick, split)) {
+        Action::JumpToLetter(timestamp, joystick, split, step)
+    } else {
+        Action::NextRom { timestamp, step }
+    }
+}
+
+/// Change the emulator (on the header) or the page of the list or, while the button of the
+/// joystick is held, the sort mode
+fn next_page_action(state: &State, joystick: i32, split: u32, timestamp: u32, step: i32) -> Action {
+    if state.button_pressed.contains_key(&(joystick, split)) {
+        Action::NextSortMode(timestamp, joystick, split, step)
+    } else if state.rom_selected == -1 {
+        Action::NextEmulator { timestamp, step }
+    } else {
+        Action::NextPage { timestamp, step }
+    }
+}
+
+struct ErrorScreen;
+
+impl Entity for ErrorScreen {
+    fn is_active(&self, state: &State) -> bool {
+        matches!(state.screen, Screen::Error(_))
+    }
+
+    #[allow(unused_must_use)]
+    fn render(&self, canvas: &mut Canvas<Window>, state: &State, resources: &mut Resources) {
+        let message = match state.screen {
+            Screen::Error(ref message) => message,
+            _ => return,
+        };
+
+        resources.font.set_line_spacing(0.75);
+        resources.font.set_pos(0, 0);
+        resources.font.texture.set_color_mod(255, 0, 0);
+        resources.font.println(canvas, "Error");
+        resources.font.println(canvas, "");
+        resources.font.texture.set_color_mod(255, 255, 255);
+        let rows = message.lines().flat_map(|line| {
+            let chars: Vec<char> = line.chars().collect();
+            let mut rows: Vec<String> = chars.chunks(42).map(|x| x.iter().collect()).collect();
+            if rows.is_empty() {
+                rows.push(String::new());
             }
+            rows
+        });
+        for row in rows.take(16) {
+            resources.font.println(canvas, &row);
+        }
+        resources.font.set_pos(0, resources.font.line_height * 19);
+        resources
+            .font
+            .println(canvas, "Press the button to go back");
+    }
+
+    fn apply_input(&self, input: &MenuInput, _app: &mut App, store: &mut Store) {
+        match input.kind {
+            MenuInputKind::Confirm | MenuInputKind::Back => store.dispatch(Action::Quit),
             _ => {}
         }
     }
@@ -868,7 +1144,19 @@ impl Entity for Root {
     fn apply_event(&self, event: &Event, app: &mut App, store: &mut Store) {
         use store::Action::*;
 
+        let typing =
+            store.get_state().is_typing_search() || store.get_state().keyboard_is_grabbing();
+
         match *event {
+            // NOTE: the keys are typed in the search or bound to the controls
+            Event::KeyUp {
+                keycode: Some(Keycode::Q),
+                ..
+            }
+            | Event::KeyUp {
+                keycode: Some(Keycode::Escape),
+                ..
+            } if typing => {}
             Event::Quit { .. }
             | Event::KeyUp {
                 keycode: Some(Keycode::Q),
@@ -883,13 +1171,7 @@ impl Entity for Root {
             }
             Event::JoyDeviceAdded {
                 which, timestamp, ..
-            } => {
-                if let Some(info) = app.open_joystick(which) {
-                    // TODO: maybe restart the application after a joystick has
-                    //       been detected to ensure the correct joystick order
-                    store.dispatch(AddJoystick(timestamp, info));
-                }
-            }
+            } => add_joystick(which, timestamp, app, store),
             Event::JoyDeviceRemoved {
                 which, timestamp, ..
             } => {
@@ -899,10 +1181,65 @@ impl Entity for Root {
             _ => {}
         }
     }
+
+    fn apply_input(&self, input: &MenuInput, _app: &mut App, store: &mut Store) {
+        use store::Action::*;
+
+        match input.kind {
+            MenuInputKind::Press => {
+                store.dispatch(PressButton(input.timestamp, input.device, input.split))
+            }
+            MenuInputKind::Confirm => {
+                store.dispatch(ReleaseButton(input.timestamp, input.device, input.split))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Open a joystick and set up the default controls of a new joystick
+fn add_joystick(which: u32, timestamp: u32, app: &mut App, store: &mut Store) {
+    use store::Action::*;
+
+    if let Some((info, name)) = app.open_joystick(which) {
+        // TODO: maybe restart the application after a joystick has
+        //       been detected to ensure the correct joystick order
+        store.dispatch(AddJoystick(timestamp, info, name));
+
+        // NOTE: the profiles of RetroArch are preferred to the mappings of SDL
+        let mapping = if info.split == 1 {
+            autoconfig::find(&store.get_state().autoconfig_dirs, &info.guid).or_else(|| {
+                app.open_game_controller(which)
+                    .map(|x| gamecontroller::parse_mapping(&x))
+            })
+        } else {
+            None
+        };
+        if let Some(mapping) = mapping {
+            store.dispatch(AddDefaultMapping(timestamp, info.guid, mapping));
+        }
+    }
+}
+
+/// What is needed to run the emulator once the launcher is closed
+pub struct LaunchCommand {
+    pub command: Vec<String>,
+    pub config: String,
+    pub rom: String,
+    /// Discs to write in a temporary m3u playlist that is given instead of the ROM
+    pub playlist: Option<Vec<String>>,
+    pub emulator_id: String,
+    /// Path of the ROM relative to the path of the emulator, for the history
+    pub rom_key: String,
+    pub player_count: usize,
+    pub backend: backend::BackendKind,
+    /// Environment variables of the emulator
+    pub env: Vec<(String, String)>,
 }
 
 pub struct Resources {
     font: Font,
+    previews: TextureCache,
 }
 
 pub struct ROMLauncher {
@@ -913,15 +1250,26 @@ pub struct ROMLauncher {
 }
 
 impl ROMLauncher {
-    pub fn new(mut app: App) -> ROMLauncher {
+    pub fn new(mut app: App, session: Option<PlaySession>, error: Option<String>) -> ROMLauncher {
         let mut store = Store::new();
         if let Err(err) = Self::load_state(&mut store) {
             error!("{}", err);
         }
+        if let Some(session) = session {
+            store.dispatch(Action::RecordPlay(session));
+        }
+        store.dispatch(Action::AddJoystick(
+            0,
+            keyboard_info(),
+            "Keyboard".to_string(),
+        ));
         store.dispatch(Action::NextEmulator {
             timestamp: 0,
             step: 0,
         });
+        if let Some(message) = error {
+            store.dispatch(Action::ShowError(message));
+        }
 
         let (w, h) = app.canvas.output_size().unwrap();
         let zoom = cmp::min(w as i32 / TV_XRES, h as i32 / TV_YRES) as f32;
@@ -950,6 +1298,59 @@ impl ROMLauncher {
         }
     }
 
+    /// Whether the launcher stays alive while the game runs
+    pub fn keep_alive(&self) -> bool {
+        self.store.get_state().keep_alive
+    }
+
+    /// Save the state, then hide the launcher and release the joysticks for the emulator
+    pub fn suspend(&mut self) {
+        self.save();
+
+        let timestamp = self.app.timer.ticks();
+        let joystick_ids: Vec<_> = self
+            .store
+            .get_state()
+            .joysticks
+            .keys()
+            .filter(|&&x| x != KEYBOARD_ID)
+            .cloned()
+            .collect();
+        for joystick_id in joystick_ids {
+            self.store
+                .dispatch(Action::RemoveJoystick(timestamp, joystick_id));
+        }
+        self.store.process();
+        self.app.suspend();
+    }
+
+    /// Show the launcher again after the game, back to the list as it was left
+    pub fn resume(&mut self, session: Option<PlaySession>, error: Option<String>) {
+        self.app.resume();
+
+        let timestamp = self.app.timer.ticks();
+        if let Some(session) = session {
+            self.store.dispatch(Action::RecordPlay(session));
+        }
+        self.store.dispatch(Action::EndGame(timestamp));
+        if let Some(message) = error {
+            self.store.dispatch(Action::ShowError(message));
+        }
+        for which in 0..self.app.joystick.num_joysticks().unwrap_or(0) {
+            add_joystick(which, timestamp, &mut self.app, &mut self.store);
+        }
+        self.store.process();
+    }
+
+    fn save(&self) {
+        if let Err(err) = save_state(&self.store) {
+            error!("could not write to save_sate: {}", err);
+        }
+        if let Err(err) = export_autoconfigs(self.store.get_state()) {
+            error!("could not export autoconfig profiles: {}", err);
+        }
+    }
+
     fn load_state(store: &mut Store) -> Result<(), io::Error> {
         let file = File::open("state.json")?;
         store.load(file);
@@ -960,11 +1361,13 @@ impl ROMLauncher {
     fn load_resources(app: &App) -> Resources {
         debug!("loading resources...");
         let font = Font::new(
-            app.load_texture("font-12.png"),
+            app.load_texture("font-12.png").unwrap(),
             "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!?()[]<>~-_+@:/'., ",
         );
 
-        Resources { font }
+        let previews = TextureCache::new(2 * PAGE_SIZE as usize);
+
+        Resources { font, previews }
     }
 
     #[allow(unused_must_use)]
@@ -975,6 +1378,7 @@ impl ROMLauncher {
             TreeBuilder::new().with_node_capacity(ENTITES).build();
         let root_id = tree.insert(Node::new(Box::new(Root {})), AsRoot).unwrap();
         tree.insert(Node::new(Box::new(List {})), UnderNode(&root_id));
+        tree.insert(Node::new(Box::new(ErrorScreen)), UnderNode(&root_id));
         let player_colors = [
             Color::RGB(0xb9, 0x00, 0x00),
             Color::RGB(0x00, 0x00, 0xb9),
@@ -1002,6 +1406,10 @@ impl ROMLauncher {
                 Node::new(Box::new(PlayerGrabInput { player_index })),
                 UnderNode(&game_launcher),
             );
+            tree.insert(
+                Node::new(Box::new(PlayerControlEditor { player_index })),
+                UnderNode(&game_launcher),
+            );
         }
         tree.insert(
             Node::new(Box::new(PlayerGrabEmulatorButtons)),
@@ -1014,6 +1422,11 @@ impl ROMLauncher {
     pub fn render(&mut self, node_ids: &[NodeId]) {
         debug!("rerender");
 
+        let state = self.store.get_state();
+        if state.screen == Screen::List {
+            self.load_preview();
+        }
+
         let state = self.store.get_state();
         for node in node_ids {
             let entity = self.tree.get(&node).unwrap().data();
@@ -1024,11 +1437,40 @@ impl ROMLauncher {
         self.app.canvas.present();
     }
 
+    /// Load the preview image of the selected ROM if it's not in the cache already
+    fn load_preview(&mut self) {
+        let state = self.store.get_state();
+        let previews = &mut self.resources.previews;
+        let path = match state
+            .get_selected_rom()
+            .and_then(|x| previews.media_path(&x.path, || state.get_emulator().media_path(x)))
+        {
+            Some(path) => path,
+            None => return,
+        };
+
+        if !self.resources.previews.contains(&path) {
+            let texture = match self.app.load_texture(&path) {
+                Ok(texture) => Some(texture),
+                Err(err) => {
+                    warn!("{}", err);
+                    None
+                }
+            };
+            self.resources.previews.insert(path, texture);
+        }
+    }
+
     pub fn apply_event(&mut self, event: Event, node_ids: &[NodeId]) -> bool {
+        let input = input::translate_event(&event, &mut self.store);
+
         for node in node_ids {
             let entity = self.tree.get(&node).unwrap().data();
 
             entity.apply_event(&event, &mut self.app, &mut self.store);
+            if let Some(ref input) = input {
+                entity.apply_input(input, &mut self.app, &mut self.store);
+            }
         }
 
         self.store.process()
@@ -1041,7 +1483,7 @@ impl ROMLauncher {
         OnlyActiveTraversal::new(&self.tree, root_id, state).collect()
     }
 
-    pub fn run_loop(&mut self) -> Option<(Vec<String>, String, String)> {
+    pub fn run_loop(&mut self) -> Option<LaunchCommand> {
         debug!("looping over events...");
         let mut rerender = true;
 
@@ -1052,8 +1494,23 @@ impl ROMLauncher {
                 self.render(&node_ids);
             }
 
-            let event = self.app.wait_event();
-            rerender = self.apply_event(event, &node_ids);
+            // NOTE: wake up every second to count down the grabs of inputs and cancel them
+            let event = match self.store.get_state().grab_deadline() {
+                Some(deadline) => {
+                    let now = self.app.timer.ticks();
+                    self.app
+                        .wait_event_timeout(cmp::min(1000, deadline.saturating_sub(now)))
+                }
+                None => Some(self.app.wait_event()),
+            };
+            rerender = match event {
+                Some(event) => self.apply_event(event, &node_ids),
+                None => {
+                    let now = self.app.timer.ticks();
+                    self.store.dispatch(Action::CancelGrab(now));
+                    self.store.process()
+                }
+            };
 
             if !self.app.is_running() {
                 break;
@@ -1063,79 +1520,91 @@ impl ROMLauncher {
         self.prepare_config()
     }
 
-    pub fn prepare_config(&self) -> Option<(Vec<String>, String, String)> {
-        use self::JoystickEvent::*;
-        use store::HatState;
+    pub fn prepare_config(&self) -> Option<LaunchCommand> {
         let state = self.store.get_state();
 
         if state.screen == store::Screen::GameLauncher {
-            let mut config = "".to_string();
             let emulator = state.get_emulator();
             let emulator_id = state.get_emulator().id.clone();
             let rom = state.get_rom().clone();
 
             let mut joystick_order = HashMap::new();
-            let mut joystick_ids: Vec<_> = state.joysticks.keys().collect();
+            let mut joystick_ids: Vec<_> = state
+                .joysticks
+                .keys()
+                .filter(|&&x| x != KEYBOARD_ID)
+                .collect();
             joystick_ids.sort();
             for (i, joystick) in joystick_ids.into_iter().enumerate() {
                 joystick_order.insert(joystick, i);
             }
 
-            for (i, player) in state
-                .players
-                .iter()
-                .filter(|x| x.is_some())
-                .map(|x| x.as_ref().unwrap())
-                .enumerate()
-            {
-                config.push_str(&format!(
-                    "input_player{}_joypad_index = {}\n",
-                    i + 1,
-                    joystick_order.get(&player.joystick).unwrap()
-                ));
-
-                let guid = state.joysticks[&player.joystick].guid;
-                for (event, control) in state
+            let mut players = Vec::new();
+            for player in state.players.iter().flatten() {
+                let info = &state.joysticks[&player.joystick];
+                let controls = state
                     .game_configs
-                    .get(&guid, &player.joystick_split, &rom.file_name)
+                    .get(&info.guid, &player.joystick_split, &rom.file_name)
                     .or_else(|| {
                         state
                             .console_configs
-                            .get(&guid, &player.joystick_split, &emulator_id)
+                            .get(&info.guid, &player.joystick_split, &emulator_id)
                     })
-                    .as_ref()
                     .unwrap()
                     .iter()
-                    .zip(emulator.controls.iter().map(|&(ref x, _)| x))
-                {
-                    config.push_str(&match *event {
-                        Unassigned => format!("// input_player{}_{} unassigned\n", i + 1, control),
-                        _ => format!(
-                            "input_player{}_{}_{}\n",
-                            i + 1,
-                            control,
-                            translate_to_retroarch_button!(event)
-                        ),
-                    });
-                }
+                    .zip(emulator.controls.iter())
+                    .map(|(event, (control, _))| (control.as_str(), event))
+                    .collect();
 
+                let mut hotkeys = Vec::new();
                 if let Some((Some(ref hotkey), Some(ref menu))) = player.grab_emulator_buttons {
-                    config.push_str(&format!(
-                        "input_enable_hotkey_{}\n",
-                        translate_to_retroarch_button!(hotkey)
-                    ));
-                    config.push_str(&format!(
-                        "input_menu_toggle_{}\n",
-                        translate_to_retroarch_button!(menu)
-                    ));
+                    hotkeys.push(("enable_hotkey", hotkey));
+                    hotkeys.push(("menu_toggle", menu));
+
+                    for (key, _) in emulator.hotkeys.iter() {
+                        match state
+                            .hotkey_actions
+                            .get(&info.guid, &player.joystick_split, key)
+                            .and_then(|x| x.first())
+                        {
+                            None | Some(JoystickEvent::Unassigned) => {}
+                            Some(event) => hotkeys.push((key.as_str(), event)),
+                        }
+                    }
                 }
 
-                config.push_str("\n");
+                players.push(PlayerControls {
+                    joypad_index: joystick_order
+                        .get(&player.joystick)
+                        .cloned()
+                        .unwrap_or(joystick_order.len()),
+                    joystick: info,
+                    controls,
+                    hotkeys,
+                });
             }
 
-            config.push_str("config_save_on_exit = false\n");
-
-            Some((emulator.command.clone(), config, rom.path))
+            let backend = emulator.backend.get();
+            let mut config = backend.config(emulator, &players);
+            let mut env = backend.env(emulator, &players);
+            let mut extra_config = Vec::new();
+            for launch_override in state.get_launch_overrides(&rom) {
+                extra_config.extend(launch_override.config.iter().cloned());
+                env.extend(launch_override.env.iter().cloned());
+            }
+            backend.extra_config(&mut config, &extra_config);
+
+            Some(LaunchCommand {
+                command: state.get_command(&rom),
+                config,
+                env,
+                backend: emulator.backend,
+                rom: rom.launch_path(emulator),
+                playlist: rom.playlist(emulator),
+                emulator_id: emulator.id.clone(),
+                rom_key: state.get_rom_key(&rom),
+                player_count: players.len(),
+            })
         } else {
             None
         }
@@ -1145,10 +1614,58 @@ impl ROMLauncher {
 impl Drop for ROMLauncher {
     fn drop(&mut self) {
         info!("exiting...");
-        if let Err(err) = save_state(&self.store) {
-            error!("could not write to save_sate: {}", err);
+        self.save();
+    }
+}
+
+/// Write the console controls of every joystick as an autoconfig profile of RetroArch. The
+/// profile of a joystick is made of the controls of all the emulators, the first emulator wins
+/// when they bind a control differently
+fn export_autoconfigs(state: &State) -> Result<(), String> {
+    use self::JoystickEvent::*;
+
+    let dir = match state.autoconfig_export_dir {
+        Some(ref dir) => dir.replace("~", dirs::home_dir().unwrap().to_str().unwrap()),
+        None => return Ok(()),
+    };
+
+    for (guid, name) in state.joystick_names.iter() {
+        if *guid == keyboard_info().guid {
+            continue;
+        }
+
+        let mut controls = HashSet::new();
+        let mut lines = Vec::new();
+        // NOTE: the controls of the other backends aren't RetroArch's
+        for emulator in state
+            .emulators
+            .iter()
+            .filter(|x| x.backend == backend::BackendKind::RetroArch)
+        {
+            let events = match state.console_configs.get(guid, &0, &emulator.id) {
+                Some(events) => events,
+                None => continue,
+            };
+
+            for (event, (control, _)) in events.iter().zip(emulator.controls.iter()) {
+                match *event {
+                    Unassigned | Key(_) => {}
+                    _ if controls.insert(control) => lines.push(format!(
+                        "input_{}{}",
+                        control,
+                        backend::retroarch_binding(event)
+                    )),
+                    _ => {}
+                }
+            }
+        }
+
+        if !lines.is_empty() {
+            autoconfig::export(Path::new(&dir), name, guid, &lines)?;
         }
     }
+
+    Ok(())
 }
 
 fn save_state(store: &Store) -> Result<(), String> {
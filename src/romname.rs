@@ -0,0 +1,317 @@
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Information found in a No-Intro or GoodTools file name like
+/// "Sonic the Hedgehog (USA, Europe) (Rev 1) [!]"
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RomName {
+    pub title: String,
+    pub regions: Vec<String>,
+    pub languages: Vec<String>,
+    pub revision: Option<String>,
+    pub version: Option<String>,
+    /// Dump flags found between square brackets: "!", "b1", "h2", "T+Eng", ...
+    pub flags: Vec<String>,
+    /// Anything else found between parentheses: "Beta", "Proto", "Hack", ...
+    pub tags: Vec<String>,
+}
+
+const REGIONS: [&str; 24] = [
+    "World",
+    "USA",
+    "Europe",
+    "Japan",
+    "Asia",
+    "Australia",
+    "Brazil",
+    "Canada",
+    "China",
+    "France",
+    "Germany",
+    "Hong Kong",
+    "Italy",
+    "Korea",
+    "Netherlands",
+    "Spain",
+    "Sweden",
+    "Taiwan",
+    "UK",
+    "Russia",
+    "Scandinavia",
+    "Latin America",
+    "Portugal",
+    "Unknown",
+];
+
+const GOODTOOLS_REGIONS: [(char, &str); 12] = [
+    ('W', "World"),
+    ('U', "USA"),
+    ('E', "Europe"),
+    ('J', "Japan"),
+    ('A', "Australia"),
+    ('B', "Brazil"),
+    ('C', "China"),
+    ('F', "France"),
+    ('G', "Germany"),
+    ('K', "Korea"),
+    ('S', "Spain"),
+    ('I', "Italy"),
+];
+
+const ARTICLES: [&str; 3] = ["The", "A", "An"];
+
+pub fn parse(name: &str) -> RomName {
+    // NOTE: the title may follow some tags: "[BIOS] Foo (USA)"
+    let mut title_start = 0;
+    loop {
+        let closing = match name[title_start..].trim_start().chars().next() {
+            Some('(') => ')',
+            Some('[') => ']',
+            _ => break,
+        };
+        match name[title_start..].find(closing) {
+            Some(x) => title_start += x + 1,
+            None => break,
+        }
+    }
+    let title_end = name[title_start..]
+        .find(['(', '['])
+        .map_or(name.len(), |x| title_start + x);
+    let mut title = clean_title(&name[title_start..title_end]);
+    if title.is_empty() {
+        title = clean_title(name);
+    }
+    let mut rom_name = RomName {
+        title,
+        ..RomName::default()
+    };
+
+    let groups = format!("{}{}", &name[..title_start], &name[title_end..]);
+    let mut rest = groups.as_str();
+    while let Some(start) = rest.find(['(', '[']) {
+        let closing = if rest[start..].starts_with('(') {
+            ')'
+        } else {
+            ']'
+        };
+        let end = match rest[start..].find(closing) {
+            Some(x) => start + x,
+            None => break,
+        };
+        let group = rest[start + 1..end].trim();
+
+        if closing == ']' {
+            rom_name.flags.push(group.to_string());
+        } else if let Some(regions) = parse_regions(group) {
+            rom_name.regions.extend(regions);
+        } else if let Some(languages) = parse_languages(group) {
+            rom_name.languages.extend(languages);
+        } else if group.starts_with("Rev ") || group.starts_with("REV") {
+            rom_name.revision = Some(group[3..].trim().to_string());
+        } else if is_version(group) {
+            rom_name.version = Some(group[1..].to_string());
+        } else if !group.is_empty() {
+            rom_name.tags.push(group.to_string());
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    rom_name
+}
+
+/// Remove extra spaces and move a trailing article to the front:
+/// "Legend of Zelda, The " becomes "The Legend of Zelda"
+fn clean_title(title: &str) -> String {
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    for article in ARTICLES.iter() {
+        let suffix = format!(", {}", article);
+        if title.ends_with(&suffix) {
+            return format!("{} {}", article, &title[..title.len() - suffix.len()]);
+        }
+    }
+
+    title
+}
+
+fn parse_regions(group: &str) -> Option<Vec<String>> {
+    let items: Vec<&str> = group.split(',').map(|x| x.trim()).collect();
+    if items.iter().all(|x| REGIONS.contains(x)) {
+        return Some(items.into_iter().map(|x| x.to_string()).collect());
+    }
+
+    if !group.is_empty() && group.len() <= 4 {
+        return group
+            .chars()
+            .map(|c| {
+                GOODTOOLS_REGIONS
+                    .iter()
+                    .find(|&&(code, _)| code == c)
+                    .map(|&(_, region)| region.to_string())
+            })
+            .collect();
+    }
+
+    None
+}
+
+fn parse_languages(group: &str) -> Option<Vec<String>> {
+    let items: Vec<&str> = group.split(',').map(|x| x.trim()).collect();
+    let is_language = |x: &&str| {
+        let mut chars = x.chars();
+        x.len() == 2
+            && chars.next().map(|c| c.is_ascii_uppercase()) == Some(true)
+            && chars.next().map(|c| c.is_ascii_lowercase()) == Some(true)
+    };
+
+    if items.iter().all(is_language) {
+        Some(items.into_iter().map(|x| x.to_string()).collect())
+    } else {
+        None
+    }
+}
+
+fn is_version(group: &str) -> bool {
+    (group.starts_with('v') || group.starts_with('V'))
+        && group.len() > 1
+        && group[1..].chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Key used to compare revisions and versions by value: "10" comes after "9" and "1.10" after
+/// "1.9"
+pub fn version_key(version: &str) -> Vec<(u32, String)> {
+    version
+        .split('.')
+        .map(|part| {
+            let digits = part.len() - part.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            (
+                part[..digits].parse().unwrap_or(0),
+                part[digits..].to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Key used to sort the titles alphabetically: lowercase and without a leading article
+pub fn sort_key(title: &str) -> String {
+    let title = title.to_lowercase();
+
+    for article in ARTICLES.iter() {
+        let prefix = format!("{} ", article.to_lowercase());
+        if title.starts_with(&prefix) {
+            return title[prefix.len()..].to_string();
+        }
+    }
+
+    title
+}
+
+/// Compare two strings with their numbers compared by value: "Mega Man 2" comes before
+/// "Mega Man 10"
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().cloned(), b.peek().cloned()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Consume the digits at the front of the characters, without the leading zeros
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+
+    while let Some(c) = chars.peek().cloned().filter(|c| c.is_ascii_digit()) {
+        digits.push(c);
+        chars.next();
+    }
+
+    digits.trim_start_matches('0').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn no_intro_name() {
+        assert_eq!(
+            parse("Sonic the Hedgehog (USA, Europe) (En,Ja) (Rev 1) (Beta) [!]"),
+            RomName {
+                title: "Sonic the Hedgehog".to_string(),
+                regions: strings(&["USA", "Europe"]),
+                languages: strings(&["En", "Ja"]),
+                revision: Some("1".to_string()),
+                version: None,
+                flags: strings(&["!"]),
+                tags: strings(&["Beta"]),
+            }
+        );
+    }
+
+    #[test]
+    fn goodtools_name() {
+        assert_eq!(
+            parse("Legend of Zelda, The (U) (V1.1) [b1]"),
+            RomName {
+                title: "The Legend of Zelda".to_string(),
+                regions: strings(&["USA"]),
+                version: Some("1.1".to_string()),
+                flags: strings(&["b1"]),
+                ..RomName::default()
+            }
+        );
+    }
+
+    #[test]
+    fn leading_tags() {
+        let rom_name = parse("[BIOS] Foo (USA)");
+        assert_eq!(rom_name.title, "Foo");
+        assert_eq!(rom_name.flags, strings(&["BIOS"]));
+        assert_eq!(rom_name.regions, strings(&["USA"]));
+
+        let rom_name = parse("(Homebrew) Bar");
+        assert_eq!(rom_name.title, "Bar");
+        assert_eq!(rom_name.tags, strings(&["Homebrew"]));
+
+        assert_eq!(parse("(Homebrew)").title, "(Homebrew)");
+    }
+
+    #[test]
+    fn revisions_by_value() {
+        assert!(version_key("10") > version_key("9"));
+        assert!(version_key("1.10") > version_key("1.9"));
+        assert!(version_key("B") > version_key("A"));
+    }
+
+    #[test]
+    fn natural_order() {
+        assert_eq!(natural_cmp("Mega Man 2", "Mega Man 10"), Ordering::Less);
+        assert_eq!(natural_cmp("Game 02", "Game 2"), Ordering::Equal);
+        assert_eq!(sort_key("The Legend of Zelda"), "legend of zelda");
+    }
+}
@@ -1,8 +1,24 @@
-use std::collections::HashMap;
-
+use std::cmp;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::archive;
+use crate::backend::BackendKind;
+use crate::command;
+use crate::dat::{self, Dat, HashCache, RomStatus};
+use crate::disc;
+use crate::gamelist;
+use crate::gamelist::GameInfo;
 use crate::joystick::*;
+use crate::keyboard::{KeyMap, KEYBOARD_ID};
+use crate::romname;
 
 pub const PAGE_SIZE: i32 = 15;
+/// Time without input after which the grab of the controls of a player is cancelled
+pub const GRAB_TIMEOUT: u32 = 10000;
+pub const VIRTUAL_SYSTEMS: [VirtualSystem; 2] =
+    [VirtualSystem::Favorites, VirtualSystem::RecentlyPlayed];
 
 macro_rules! modify_player {
     ($players:expr, $joystick:expr, $split:expr, $closure:expr) => {
@@ -25,6 +41,10 @@ pub struct State {
     pub page_count: i32,
     pub emulator_selected: i32,
     pub emulators: Vec<Emulator>,
+    pub virtual_system: Option<VirtualSystem>,
+    pub library: Vec<Rom>,
+    pub folder: String,
+    pub folder_positions: HashMap<String, (i32, i32)>,
     pub roms: Result<Vec<Rom>, String>,
     pub rom_selected: i32,
     pub rom_count: i32,
@@ -33,11 +53,177 @@ pub struct State {
     pub players: [Option<Player>; 10],
     pub console_configs: JoystickConfig,
     pub game_configs: JoystickConfig,
+    /// The hotkey and menu buttons of the emulators, by emulator id or "*" for all of them
+    pub hotkey_configs: JoystickConfig,
+    /// The buttons of the hotkeys of RetroArch, by hotkey
+    pub hotkey_actions: JoystickConfig,
+    /// The changes to the command of the emulator, by ROM
+    pub game_overrides: HashMap<String, LaunchOverride>,
+    pub button_pressed: HashMap<(i32, u32), ButtonPress>,
+    /// Text typed on the keyboard to filter the ROMs, if searching
+    pub search: Option<String>,
+    pub sort_mode: SortMode,
+    pub key_map: KeyMap,
+    /// Folders of RetroArch's autoconfig profiles used to set up the controls of new joysticks
+    pub autoconfig_dirs: Vec<String>,
+    /// Folder where the console controls are written as autoconfig profiles of RetroArch
+    pub autoconfig_export_dir: Option<String>,
+    /// Names of all the joysticks ever plugged
+    pub joystick_names: HashMap<JoystickGuid, String>,
+    /// Keep the launcher running, hidden, while a game runs
+    pub keep_alive: bool,
+    pub favorites: HashMap<String, HashSet<String>>,
+    pub history: HashMap<String, HashMap<String, PlayStats>>,
 }
 
 impl State {
+    /// The emulator of the list or, in a virtual system, the emulator of the selected ROM
     pub fn get_emulator(&self) -> &Emulator {
-        &self.emulators[self.emulator_selected as usize]
+        let emulator_id = match self.virtual_system {
+            Some(_) => self.get_selected_rom().map(|x| &x.emulator_id),
+            None => None,
+        };
+
+        emulator_id
+            .and_then(|x| self.emulators.iter().find(|y| y.id == *x))
+            .unwrap_or(&self.emulators[self.emulator_selected as usize])
+    }
+
+    /// Name of the emulator or virtual system of the list
+    pub fn get_system_name(&self) -> &str {
+        match self.virtual_system {
+            Some(ref x) => x.name(),
+            None => &self.emulators[self.emulator_selected as usize].name,
+        }
+    }
+
+    pub fn has_preview(&self) -> bool {
+        match self.virtual_system {
+            Some(_) => self.emulators.iter().any(|x| x.media.is_some()),
+            None => self.emulators[self.emulator_selected as usize]
+                .media
+                .is_some(),
+        }
+    }
+
+    /// Sort the ROMs with the sort mode (the recently played virtual system is always sorted by
+    /// date)
+    pub fn sort_roms(&self, roms: &mut [Rom]) {
+        let sort_mode = match self.virtual_system {
+            Some(VirtualSystem::RecentlyPlayed) => SortMode::RecentlyPlayed,
+            _ => self.sort_mode,
+        };
+        let name = |x: &Rom| romname::sort_key(x.display_name());
+        let stats = |x: &Rom| self.get_play_stats(x).cloned().unwrap_or_default();
+
+        roms.sort_by(|a, b| {
+            match sort_mode {
+                SortMode::Name => cmp::Ordering::Equal,
+                SortMode::MostPlayed => stats(b).count.cmp(&stats(a).count),
+                SortMode::RecentlyPlayed => stats(b).last_played.cmp(&stats(a).last_played),
+                SortMode::Year => {
+                    let year = |x: &Rom| x.info.as_ref().and_then(|x| x.year()).unwrap_or(u32::MAX);
+                    year(a).cmp(&year(b))
+                }
+                SortMode::VerifiedFirst => {
+                    let rank = |x: &Rom| match x.status {
+                        RomStatus::Verified => 0,
+                        RomStatus::Unknown => 1,
+                        RomStatus::Bad => 2,
+                    };
+                    rank(a).cmp(&rank(b))
+                }
+            }
+            .then_with(|| romname::natural_cmp(&name(a), &name(b)))
+        });
+    }
+
+    pub fn get_play_stats(&self, rom: &Rom) -> Option<&PlayStats> {
+        self.history
+            .get(&rom.emulator_id)
+            .and_then(|x| x.get(&self.get_rom_key(rom)))
+    }
+
+    pub fn is_favorite(&self, rom: &Rom) -> bool {
+        self.favorites
+            .get(&rom.emulator_id)
+            .map(|x| x.contains(&self.get_rom_key(rom)))
+            == Some(true)
+    }
+
+    /// Path of the ROM relative to the path of its emulator, the file name when the emulator is
+    /// unknown (ROMs of the same name in different sub-folders are different games)
+    pub fn get_rom_key(&self, rom: &Rom) -> String {
+        self.emulators
+            .iter()
+            .find(|x| x.id == rom.emulator_id)
+            .map_or_else(|| rom.file_name.clone(), |x| x.relative_path(rom))
+    }
+
+    /// The keys are typed in the search (it is kept when a game is launched from its results)
+    pub fn is_typing_search(&self) -> bool {
+        self.search.is_some() && self.screen == Screen::List
+    }
+
+    /// The player of the keyboard is binding keys to controls
+    pub fn keyboard_is_grabbing(&self) -> bool {
+        self.players.iter().flatten().any(|x| {
+            x.joystick == KEYBOARD_ID
+                && (x.grab_input.is_some() || x.grab_emulator_buttons.is_some())
+        })
+    }
+
+    /// A player of the part of the joystick is binding inputs to controls
+    pub fn is_grabbing(&self, joystick: i32, split: u32) -> bool {
+        self.players.iter().flatten().any(|x| {
+            x.joystick == joystick
+                && x.joystick_split == split
+                && (x.grab_input.is_some() || x.grab_emulator_buttons.is_some())
+        })
+    }
+
+    /// When the first of the grabs of inputs in progress is cancelled
+    pub fn grab_deadline(&self) -> Option<u32> {
+        self.players
+            .iter()
+            .flatten()
+            .filter(|x| x.grab_input.is_some())
+            .map(|x| x.grab_timestamp + GRAB_TIMEOUT)
+            .min()
+    }
+
+    /// The core of the emulator chosen for a ROM and the override of the ROM, in this order
+    pub fn get_launch_overrides(&self, rom: &Rom) -> Vec<&LaunchOverride> {
+        let game_override = self.game_overrides.get(&rom.file_name);
+        let core = game_override.and_then(|x| {
+            self.get_emulator()
+                .cores
+                .iter()
+                .find(|core| !x.name.is_empty() && core.name == x.name)
+        });
+
+        core.into_iter().chain(game_override).collect()
+    }
+
+    /// The command of the emulator for a ROM, with its overrides
+    pub fn get_command(&self, rom: &Rom) -> Vec<String> {
+        let mut command = self.get_emulator().command.clone();
+
+        for launch_override in self.get_launch_overrides(rom) {
+            if !launch_override.command.is_empty() {
+                command = launch_override.command.clone();
+            }
+            command.extend(launch_override.args.iter().cloned());
+        }
+
+        command
+    }
+
+    /// Name of the core of the emulator chosen for a ROM, empty for the default command
+    pub fn get_core_name(&self, rom: &Rom) -> &str {
+        self.game_overrides
+            .get(&rom.file_name)
+            .map_or("", |x| x.name.as_str())
     }
 
     pub fn get_controls(&self) -> &Vec<(String, String)> {
@@ -63,6 +249,18 @@ impl State {
             .unwrap()
     }
 
+    /// The ROM under the cursor of the list if any
+    pub fn get_selected_rom(&self) -> Option<&Rom> {
+        if self.rom_selected < 0 || self.rom_selected >= PAGE_SIZE {
+            return None;
+        }
+
+        self.roms
+            .as_ref()
+            .ok()
+            .and_then(|x| x.get((self.page_index * PAGE_SIZE + self.rom_selected) as usize))
+    }
+
     pub fn player_needs_setup_controls(&self, player_index: usize) -> bool {
         match self.players[player_index].as_ref() {
             Some(player) => {
@@ -100,6 +298,66 @@ impl State {
         }
     }
 
+    /// The console or game controls of a player when they are set up, with one event per control
+    /// of the emulator
+    pub fn get_player_controls(
+        &self,
+        player_index: usize,
+        target: GrabControl,
+    ) -> Option<Vec<JoystickEvent>> {
+        let player = self.players[player_index].as_ref()?;
+        let guid = &self.joysticks[&player.joystick].guid;
+        let mut mapping = match target {
+            GrabControl::Console => {
+                self.console_configs
+                    .get(guid, &player.joystick_split, &self.get_emulator().id)
+            }
+            GrabControl::Game => {
+                self.game_configs
+                    .get(guid, &player.joystick_split, &self.get_rom().file_name)
+            }
+        }?
+        .clone();
+        mapping.resize(self.get_controls().len(), JoystickEvent::Unassigned);
+
+        Some(mapping)
+    }
+
+    /// The key of the hotkey and menu buttons of a joystick for the current emulator: its id when
+    /// it has its own buttons, "*" otherwise
+    fn hotkey_config_key(&self, guid: &JoystickGuid, split: u32) -> String {
+        let emulator_id = &self.get_emulator().id;
+
+        if self.hotkey_configs.contains_key(guid, &split, emulator_id) {
+            emulator_id.clone()
+        } else {
+            "*".to_string()
+        }
+    }
+
+    /// The saved hotkey and menu buttons of a joystick for the current emulator
+    /// The hotkey and menu buttons are only given to RetroArch
+    pub fn asks_emulator_buttons(&self) -> bool {
+        self.get_emulator().backend == BackendKind::RetroArch
+    }
+
+    pub fn get_emulator_buttons(
+        &self,
+        joystick_id: i32,
+        split: u32,
+    ) -> Option<(JoystickEvent, JoystickEvent)> {
+        let guid = &self.joysticks.get(&joystick_id)?.guid;
+
+        match self
+            .hotkey_configs
+            .get(guid, &split, &self.hotkey_config_key(guid, split))?
+            .as_slice()
+        {
+            [hotkey, menu] => Some((hotkey.clone(), menu.clone())),
+            _ => None,
+        }
+    }
+
     pub fn joystick_has_game_controls(&self, joystick_id: i32, split: u32) -> bool {
         let guid = &self.joysticks[&joystick_id].guid;
         let rom = &self.get_rom().file_name;
@@ -114,12 +372,143 @@ pub struct SaveState {
     emulators: Vec<Emulator>,
     console_configs: JoystickConfig,
     game_configs: JoystickConfig,
+    #[serde(default)]
+    favorites: HashMap<String, HashSet<String>>,
+    #[serde(default)]
+    history: HashMap<String, HashMap<String, PlayStats>>,
+    #[serde(default)]
+    sort_mode: SortMode,
+    #[serde(default)]
+    key_map: KeyMap,
+    #[serde(default)]
+    autoconfig_dirs: Vec<String>,
+    #[serde(default)]
+    autoconfig_export_dir: Option<String>,
+    #[serde(default)]
+    joystick_names: HashMap<JoystickGuid, String>,
+    #[serde(default)]
+    hotkey_configs: JoystickConfig,
+    #[serde(default)]
+    hotkey_actions: JoystickConfig,
+    #[serde(default)]
+    game_overrides: HashMap<String, LaunchOverride>,
+    #[serde(default)]
+    keep_alive: bool,
+}
+
+/// Order of the ROMs in the list
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum SortMode {
+    /// Alphabetical, ignoring the case and the leading articles
+    #[default]
+    Name,
+    MostPlayed,
+    RecentlyPlayed,
+    Year,
+    VerifiedFirst,
+}
+
+pub const SORT_MODES: [SortMode; 5] = [
+    SortMode::Name,
+    SortMode::MostPlayed,
+    SortMode::RecentlyPlayed,
+    SortMode::Year,
+    SortMode::VerifiedFirst,
+];
+
+impl SortMode {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SortMode::Name => "Name",
+            SortMode::MostPlayed => "Most played",
+            SortMode::RecentlyPlayed => "Last played",
+            SortMode::Year => "Year",
+            SortMode::VerifiedFirst => "Verified",
+        }
+    }
+}
+
+/// A button of a joystick that is currently held
+#[derive(Clone, Copy, Debug)]
+pub struct ButtonPress {
+    pub timestamp: u32,
+    /// The button has been used together with a direction
+    pub combined: bool,
+}
+
+/// How much a ROM has been played
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PlayStats {
+    pub count: u32,
+    /// Start of the last session in seconds since the epoch
+    pub last_played: u64,
+    /// Total time played in seconds
+    pub playtime: u64,
+}
+
+impl PlayStats {
+    /// One line summary of the stats: "Played 3 times - 1h05m - 2 days ago"
+    pub fn summary(&self) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_secs());
+        let playtime = if self.playtime < 60 {
+            "<1m".to_string()
+        } else if self.playtime < 3600 {
+            format!("{}m", self.playtime / 60)
+        } else {
+            format!("{}h{:02}m", self.playtime / 3600, self.playtime % 3600 / 60)
+        };
+        let last_played = match now.saturating_sub(self.last_played) / 86400 {
+            0 => "today".to_string(),
+            1 => "yesterday".to_string(),
+            x => format!("{} days ago", x),
+        };
+
+        format!(
+            "Played {} time{} - {} - {}",
+            self.count,
+            if self.count == 1 { "" } else { "s" },
+            playtime,
+            last_played
+        )
+    }
+}
+
+/// A game played by the emulator that has been launched by the previous run
+#[derive(Clone, Debug)]
+pub struct PlaySession {
+    pub emulator_id: String,
+    /// Path of the ROM relative to the path of the emulator
+    pub rom_key: String,
+    /// Start of the session in seconds since the epoch
+    pub started: u64,
+    /// Duration of the session in seconds
+    pub duration: u64,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Screen {
     List,
     GameLauncher,
+    /// A message shown until the button is pressed
+    Error(String),
+}
+
+/// A list of ROMs coming from all the emulators
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VirtualSystem {
+    Favorites,
+    RecentlyPlayed,
+}
+
+impl VirtualSystem {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            VirtualSystem::Favorites => "Favorites",
+            VirtualSystem::RecentlyPlayed => "Recently played",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -131,13 +520,196 @@ pub struct Emulator {
     pub command: Vec<String>,
     pub extensions: Vec<String>,
     pub exclude: Vec<String>,
+    /// How many levels of sub-folders are scanned for ROMs (0 = only the top folder)
+    #[serde(default)]
+    pub max_depth: u32,
+    #[serde(default)]
+    pub archive_launch: ArchiveLaunch,
+    /// Give a temporary m3u playlist to the emulator when the game has multiple discs
+    #[serde(default)]
+    pub generate_m3u: bool,
+    /// Regions in order of preference, only the best region of a game is listed when set
+    #[serde(default)]
+    pub preferred_regions: Vec<String>,
+    /// Logiqx XML DAT files used to verify the ROMs
+    #[serde(default)]
+    pub dat_files: Vec<String>,
+    /// Image of the selected ROM shown next to the list, placeholders: {emulator.path},
+    /// {emulator.id}, {rom.name}, {rom.title}, {rom.file_name} and {rom.folder}
+    #[serde(default)]
+    pub media: Option<String>,
+    /// Hotkeys of RetroArch asked to the first player after the menu button: ["save_state",
+    /// "Save state"] gives `input_save_state_btn`
+    #[serde(default)]
+    pub hotkeys: Vec<(String, String)>,
+    /// How the controls are given to the emulator (RetroArch by default)
+    #[serde(default)]
+    pub backend: BackendKind,
+    /// Other ways to run the games that can be chosen per game
+    #[serde(default)]
+    pub cores: Vec<LaunchOverride>,
 }
 
-#[derive(Clone, Debug)]
+/// Changes to the command of an emulator: a core of the emulator or the override of a game. The
+/// override of a game is applied over the core of the emulator with the same name
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct LaunchOverride {
+    pub name: String,
+    /// Replaces the command of the emulator when set
+    pub command: Vec<String>,
+    /// Added to the command
+    pub args: Vec<String>,
+    /// Lines added to the configuration file
+    pub config: Vec<String>,
+    /// Environment variables of the emulator
+    pub env: Vec<(String, String)>,
+}
+
+impl LaunchOverride {
+    fn is_empty(&self) -> bool {
+        self.name.is_empty()
+            && self.command.is_empty()
+            && self.args.is_empty()
+            && self.config.is_empty()
+            && self.env.is_empty()
+    }
+}
+
+impl Emulator {
+    /// Path of a ROM relative to the path of the emulator: "folder/file.zip"
+    pub fn relative_path(&self, rom: &Rom) -> String {
+        let path = self
+            .path
+            .replace("~", dirs::home_dir().unwrap().to_str().unwrap());
+        Path::new(&rom.path)
+            .strip_prefix(&path)
+            .ok()
+            .and_then(|x| x.to_str())
+            .map_or_else(|| rom.file_name.clone(), |x| x.to_string())
+    }
+
+    /// Path of the preview image of a ROM, the images of the gamelist.xml are used when the file
+    /// of the media pattern doesn't exist
+    pub fn media_path(&self, rom: &Rom) -> Option<String> {
+        let pattern = self.media.as_ref()?;
+        if rom.kind != RomKind::File {
+            return None;
+        }
+
+        // NOTE: the names of the ROMs may contain "~"
+        let home_dir = dirs::home_dir().unwrap();
+        let path = pattern
+            .replace("~", home_dir.to_str().unwrap())
+            .replace(
+                "{emulator.path}",
+                &self.path.replace("~", home_dir.to_str().unwrap()),
+            )
+            .replace("{emulator.id}", &self.id)
+            .replace("{rom.name}", &rom.name)
+            .replace("{rom.title}", &rom.title)
+            .replace("{rom.file_name}", &rom.file_name)
+            .replace("{rom.folder}", &rom.folder);
+
+        if Path::new(&path).is_file() {
+            Some(path)
+        } else {
+            rom.info
+                .as_ref()
+                .and_then(|x| x.image.clone().or_else(|| x.thumbnail.clone()))
+        }
+    }
+}
+
+/// What is given to the emulator when the ROM is stored in an archive
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub enum ArchiveLaunch {
+    /// The path of the archive itself
+    #[default]
+    Archive,
+    /// The path of the file inside of the archive: "archive.zip#file.bin"
+    Entry,
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct Rom {
     pub path: String,
     pub name: String,
     pub file_name: String,
+    pub emulator_id: String,
+    /// Name to display, without the tags of the file name
+    pub title: String,
+    pub regions: Vec<String>,
+    pub languages: Vec<String>,
+    pub revision: Option<String>,
+    pub version: Option<String>,
+    /// Dump flags of the file name: "!" (verified), "b" (bad dump), "h" (hack), ...
+    pub flags: Vec<String>,
+    /// Other tags of the file name: "Beta", "Proto", "Hack", ...
+    pub tags: Vec<String>,
+    pub status: RomStatus,
+    /// Name of the game in the DAT files
+    pub canonical_name: Option<String>,
+    /// Metadata of the gamelist.xml
+    pub info: Option<GameInfo>,
+    /// File to launch inside of the archive when the ROM is an archive (zip, 7z)
+    pub archive_entry: Option<String>,
+    /// Path of every disc of the game when it has more than one (first disc first)
+    pub discs: Vec<String>,
+    /// Folder containing the ROM, relative to the emulator's path ("" = top folder)
+    pub folder: String,
+    pub kind: RomKind,
+}
+
+impl Rom {
+    /// Name shown in the list: the name of the gamelist.xml or the title of the file name
+    pub fn display_name(&self) -> &str {
+        self.info
+            .as_ref()
+            .and_then(|x| x.name.as_ref())
+            .unwrap_or(&self.title)
+    }
+
+    fn parse_name(&mut self, name: &str) {
+        let rom_name = romname::parse(name);
+
+        self.title = rom_name.title;
+        self.regions = rom_name.regions;
+        self.languages = rom_name.languages;
+        self.revision = rom_name.revision;
+        self.version = rom_name.version;
+        self.flags = rom_name.flags;
+        self.tags = rom_name.tags;
+    }
+
+    pub fn launch_path(&self, emulator: &Emulator) -> String {
+        match self.archive_entry {
+            Some(ref entry) if emulator.archive_launch == ArchiveLaunch::Entry => {
+                format!("{}#{}", self.path, entry)
+            }
+            _ => self.path.clone(),
+        }
+    }
+
+    /// The discs that must be written to a temporary playlist to launch the game
+    pub fn playlist(&self, emulator: &Emulator) -> Option<Vec<String>> {
+        if emulator.generate_m3u
+            && self.discs.len() > 1
+            && !disc::is_playlist(Path::new(&self.path))
+        {
+            Some(self.discs.clone())
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum RomKind {
+    #[default]
+    File,
+    /// An entry of the list that opens the given folder (relative to the emulator's path)
+    Folder(String),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -160,9 +732,27 @@ pub enum JoystickEvent {
     Button(u8),
     Hat(u8, HatState),
     Axis(u8, AxisState),
+    /// A key of the keyboard, by its name in RetroArch
+    Key(String),
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+impl JoystickEvent {
+    /// Short description of the event for the menus: "Button 3", "Hat 0 up", "Axis 1-", "Key a"
+    pub fn label(&self) -> String {
+        use self::JoystickEvent::*;
+
+        match *self {
+            Unassigned => "None".to_string(),
+            Button(x) => format!("Button {}", x),
+            Hat(x, ref state) => format!("Hat {} {}", x, format!("{:?}", state).to_lowercase()),
+            Axis(x, AxisState::Positive) => format!("Axis {}+", x),
+            Axis(x, AxisState::Negative) => format!("Axis {}-", x),
+            Key(ref x) => format!("Key {}", x),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct JoystickConfig(HashMap<JoystickGuid, HashMap<u32, HashMap<String, Vec<JoystickEvent>>>>);
 
 impl JoystickConfig {
@@ -246,13 +836,24 @@ pub struct Player {
     pub joystick_split: u32,
     pub menu: PlayerMenu,
     pub grab_input: Option<(GrabControl, Vec<JoystickEvent>)>,
+    /// When the grab of the inputs started or the last input was grabbed
+    pub grab_timestamp: u32,
+    /// The last input grabbed was ignored because another control has it
+    pub grab_in_use: bool,
     pub grab_emulator_buttons: Option<(Option<JoystickEvent>, Option<JoystickEvent>)>,
+    /// The buttons of the hotkeys of the emulator grabbed after the menu button
+    pub grab_hotkeys: Option<Vec<JoystickEvent>>,
+    pub edit_controls: Option<ControlEditor>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum PlayerMenu {
     Controls,
     Ready,
+    /// The core of the emulator (first player only)
+    Core,
+    /// Choosing the core of the emulator
+    CoreChoice,
     Leave,
     Waiting,
     ConsoleControls,
@@ -261,30 +862,99 @@ pub enum PlayerMenu {
     ControlsExit,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum GrabControl {
     Console,
     Game,
 }
 
+/// Editor of the controls of a player, one control at a time
+#[derive(Clone, Debug)]
+pub struct ControlEditor {
+    pub target: GrabControl,
+    pub entry: EditorEntry,
+    /// The action chosen for the selected control
+    pub action: Option<EditorAction>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditorEntry {
+    /// Bind all the controls again
+    All,
+    /// The index of a control of the emulator
+    Control(usize),
+    Exit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditorAction {
+    Rebind,
+    Unassign,
+    Cancel,
+}
+
+impl ControlEditor {
+    /// Move to another entry or, when a control is selected, to another action
+    fn step(&mut self, controls_len: usize, step: i32) {
+        use self::EditorAction::*;
+        use self::EditorEntry::*;
+
+        if let Some(action) = self.action {
+            let actions = [Rebind, Unassign, Cancel];
+            let index = actions.iter().position(|x| *x == action).unwrap() as i32 + step;
+            self.action = Some(actions[index.clamp(0, 2) as usize]);
+        } else {
+            let index = match self.entry {
+                All => 0,
+                Control(x) => x as i32 + 1,
+                Exit => controls_len as i32 + 1,
+            } + step;
+            self.entry = match index.clamp(0, controls_len as i32 + 1) {
+                0 => All,
+                x if x as usize > controls_len => Exit,
+                x => Control(x as usize - 1),
+            };
+        }
+    }
+}
+
 /// An Enum of all the possible actions in the application
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug)]
 pub enum Action {
     Initialize(u32, SaveState),
     LoadRoms { roms: Result<Vec<Rom>, String> },
+    OpenFolder { timestamp: u32, folder: String },
     NextRom { timestamp: u32, step: i32 },
     NextPage { timestamp: u32, step: i32 },
     NextEmulator { timestamp: u32, step: i32 },
-    AddJoystick(u32, JoystickInfo),
+    ToggleFavorite { timestamp: u32 },
+    PressButton(u32, i32, u32),
+    ReleaseButton(u32, i32, u32),
+    JumpToLetter(u32, i32, u32, i32),
+    Search(u32, Option<String>),
+    NextSortMode(u32, i32, u32, i32),
+    RecordPlay(PlaySession),
+    EndGame(u32),
+    ShowError(String),
+    AddJoystick(u32, JoystickInfo, String),
+    AddDefaultMapping(u32, JoystickGuid, HashMap<String, JoystickEvent>),
     RemoveJoystick(u32, i32),
     LaunchGame(u32, i32, u32),
     AddPlayer(u32, i32, u32),
     NextPlayerMenu(u32, i32, u32),
     PrevPlayerMenu(u32, i32, u32),
     GoPlayerMenu(u32, i32, u32),
+    NextControlEditor(u32, i32, u32, i32),
+    GoControlEditor(u32, i32, u32),
+    BackControlEditor(u32, i32, u32),
+    NextCore { timestamp: u32, step: i32 },
     BindPlayerJoystickEvent(u32, usize, JoystickEvent),
+    CancelGrab(u32),
     UpdateJoystickLastAction(u32, i32, u32),
+    AskEmulatorButtons(u32),
     BindEmulatorButton(u32, JoystickEvent),
+    BindEmulatorHotkey(u32, JoystickEvent),
     Quit,
     Rerender(u32),
 }
@@ -301,9 +971,32 @@ fn reduce(state: State, action: Action) -> State {
             emulators: save_state.emulators,
             console_configs: save_state.console_configs,
             game_configs: save_state.game_configs,
+            hotkey_configs: save_state.hotkey_configs,
+            hotkey_actions: save_state.hotkey_actions,
+            game_overrides: save_state.game_overrides,
+            favorites: save_state.favorites,
+            history: save_state.history,
+            sort_mode: save_state.sort_mode,
+            key_map: save_state.key_map,
+            autoconfig_dirs: save_state.autoconfig_dirs,
+            autoconfig_export_dir: save_state.autoconfig_export_dir,
+            joystick_names: save_state
+                .joystick_names
+                .into_iter()
+                .chain(state.joystick_names)
+                .collect(),
+            keep_alive: save_state.keep_alive,
             ..state
         },
         LoadRoms { roms } => {
+            let (library, roms) = match roms {
+                Err(err) => (Vec::new(), Err(err)),
+                Ok(mut library) => {
+                    state.sort_roms(&mut library);
+                    let roms = list_folder(&library, "");
+                    (library, Ok(roms))
+                }
+            };
             let rom_count = match roms {
                 Err(_) => 0,
                 Ok(ref roms) => roms.len() as i32,
@@ -314,7 +1007,32 @@ fn reduce(state: State, action: Action) -> State {
                 page_index: 0,
                 rom_selected: -1,
                 rom_count,
+                library,
+                folder: String::new(),
+                folder_positions: HashMap::new(),
                 roms,
+                search: None,
+                ..state
+            }
+        }
+        OpenFolder { timestamp, folder } => {
+            let mut folder_positions = state.folder_positions;
+            folder_positions.insert(state.folder, (state.page_index, state.rom_selected));
+            let roms = list_folder(&state.library, &folder);
+            let rom_count = roms.len() as i32;
+            let page_count = (rom_count - 1).wrapping_div(PAGE_SIZE) + 1;
+            let (page_index, rom_selected) =
+                folder_positions.get(&folder).cloned().unwrap_or((0, 0));
+
+            State {
+                timestamp,
+                page_count,
+                page_index: cmp::min(page_index, page_count - 1),
+                rom_selected,
+                rom_count,
+                folder,
+                folder_positions,
+                roms: Ok(roms),
                 ..state
             }
         }
@@ -378,27 +1096,229 @@ fn reduce(state: State, action: Action) -> State {
             }
         }
         NextEmulator { timestamp, step } => {
-            let max = state.emulators.len() as i32 - 1;
-            let mut emulator_selected = state.emulator_selected + step;
-            if emulator_selected < 0 {
-                emulator_selected = max;
-            } else if emulator_selected > max {
-                emulator_selected = 0;
+            // NOTE: the virtual systems are placed before the emulators
+            let virtual_count = VIRTUAL_SYSTEMS.len() as i32;
+            let max = virtual_count + state.emulators.len() as i32 - 1;
+            let mut selected = match state.virtual_system {
+                Some(x) => VIRTUAL_SYSTEMS.iter().position(|y| *y == x).unwrap() as i32,
+                None => virtual_count + state.emulator_selected,
+            } + step;
+            if selected < 0 {
+                selected = max;
+            } else if selected > max {
+                selected = 0;
+            }
+
+            if selected < virtual_count {
+                State {
+                    timestamp,
+                    virtual_system: Some(VIRTUAL_SYSTEMS[selected as usize]),
+                    ..state
+                }
+            } else {
+                State {
+                    timestamp,
+                    virtual_system: None,
+                    emulator_selected: selected - virtual_count,
+                    ..state
+                }
+            }
+        }
+        ToggleFavorite { timestamp } => {
+            let selected = state
+                .get_selected_rom()
+                .filter(|x| x.kind == RomKind::File)
+                .map(|x| (x.emulator_id.clone(), state.get_rom_key(x)));
+            let mut favorites = state.favorites;
+
+            if let Some((emulator_id, key)) = selected {
+                let files = favorites.entry(emulator_id).or_default();
+                if !files.remove(&key) {
+                    files.insert(key);
+                }
+            }
+
+            State {
+                timestamp,
+                favorites,
+                ..state
+            }
+        }
+        RecordPlay(session) => {
+            let mut history = state.history;
+            let stats = history
+                .entry(session.emulator_id)
+                .or_default()
+                .entry(session.rom_key)
+                .or_default();
+            stats.count += 1;
+            stats.last_played = session.started;
+            stats.playtime += session.duration;
+
+            State { history, ..state }
+        }
+        EndGame(timestamp) => State {
+            timestamp,
+            screen: Screen::List,
+            players: [None, None, None, None, None, None, None, None, None, None],
+            button_pressed: HashMap::new(),
+            ..state
+        },
+        ShowError(message) => State {
+            screen: Screen::Error(message),
+            ..state
+        },
+        PressButton(timestamp, joystick_id, split) => {
+            let mut button_pressed = state.button_pressed;
+            button_pressed.insert(
+                (joystick_id, split),
+                ButtonPress {
+                    timestamp,
+                    combined: false,
+                },
+            );
+
+            State {
+                timestamp,
+                button_pressed,
+                ..state
+            }
+        }
+        ReleaseButton(timestamp, joystick_id, split) => {
+            let mut button_pressed = state.button_pressed;
+            button_pressed.remove(&(joystick_id, split));
+
+            State {
+                timestamp,
+                button_pressed,
+                ..state
+            }
+        }
+        JumpToLetter(timestamp, joystick, split, step) => {
+            let mut button_pressed = state.button_pressed;
+            if let Some(press) = button_pressed.get_mut(&(joystick, split)) {
+                press.combined = true;
+            }
+            let index = match state.roms {
+                Ok(ref roms) => jump_to_letter(
+                    roms,
+                    state.page_index * PAGE_SIZE + cmp::max(state.rom_selected, 0),
+                    step,
+                ),
+                Err(_) => None,
+            };
+
+            match index {
+                Some(index) => State {
+                    timestamp,
+                    button_pressed,
+                    page_index: index / PAGE_SIZE,
+                    rom_selected: index % PAGE_SIZE,
+                    ..state
+                },
+                None => State {
+                    timestamp,
+                    button_pressed,
+                    ..state
+                },
+            }
+        }
+        Search(timestamp, search) => {
+            if state.roms.is_err() {
+                return State { timestamp, ..state };
             }
 
+            let roms = list_view(&state.library, &state.folder, &search);
+            let rom_count = roms.len() as i32;
+
             State {
                 timestamp,
-                emulator_selected,
+                page_count: (rom_count - 1).wrapping_div(PAGE_SIZE) + 1,
+                page_index: 0,
+                rom_selected: 0,
+                rom_count,
+                roms: Ok(roms),
+                search,
                 ..state
             }
         }
-        AddJoystick(timestamp, info) => {
+        NextSortMode(timestamp, joystick, split, step) => {
+            let mut button_pressed = state.button_pressed;
+            if let Some(press) = button_pressed.get_mut(&(joystick, split)) {
+                press.combined = true;
+            }
+            let current = state.sort_mode;
+            let index = SORT_MODES.iter().position(|x| *x == current).unwrap() as i32;
+            let sort_mode = SORT_MODES[(index + step).rem_euclid(SORT_MODES.len() as i32) as usize];
+
+            let state = State {
+                timestamp,
+                button_pressed,
+                sort_mode,
+                ..state
+            };
+            if state.roms.is_err() {
+                return state;
+            }
+
+            let selected = state.get_selected_rom().cloned();
+            let mut library = state.library.clone();
+            state.sort_roms(&mut library);
+            let roms = list_view(&library, &state.folder, &state.search);
+            let index = selected
+                .and_then(|x| {
+                    roms.iter()
+                        .position(|y| y.path == x.path && y.name == x.name)
+                })
+                .map_or(0, |x| x as i32);
+
+            State {
+                page_index: index / PAGE_SIZE,
+                rom_selected: if state.rom_selected == -1 {
+                    -1
+                } else {
+                    index % PAGE_SIZE
+                },
+                library,
+                roms: Ok(roms),
+                ..state
+            }
+        }
+        AddJoystick(timestamp, info, name) => {
             let mut joysticks = state.joysticks;
             joysticks.insert(info.instance_id, info);
+            let mut joystick_names = state.joystick_names;
+            joystick_names.insert(info.guid, name);
 
             State {
                 timestamp,
                 joysticks,
+                joystick_names,
+                ..state
+            }
+        }
+        AddDefaultMapping(timestamp, guid, mapping) => {
+            let mut console_configs = state.console_configs;
+
+            // NOTE: the mapping is for the whole joystick so it is given to its first split
+            for emulator in state.emulators.iter() {
+                if console_configs.contains_key(&guid, &0, &emulator.id) {
+                    continue;
+                }
+
+                let events: Vec<JoystickEvent> = emulator
+                    .controls
+                    .iter()
+                    .map(|(x, _)| mapping.get(x).cloned().unwrap_or(JoystickEvent::Unassigned))
+                    .collect();
+                if events.iter().any(|x| *x != JoystickEvent::Unassigned) {
+                    console_configs.insert(guid, 0, emulator.id.clone(), events);
+                }
+            }
+
+            State {
+                timestamp,
+                console_configs,
                 ..state
             }
         }
@@ -427,6 +1347,14 @@ fn reduce(state: State, action: Action) -> State {
             }
         }
         LaunchGame(timestamp, ..) => {
+            if let Err(err) = command::has_placeholders(&state.get_command(state.get_rom())) {
+                return State {
+                    timestamp,
+                    screen: Screen::Error(format!("Invalid command: {}", err)),
+                    ..state
+                };
+            }
+
             let players = [None, None, None, None, None, None, None, None, None, None];
 
             State {
@@ -461,7 +1389,11 @@ fn reduce(state: State, action: Action) -> State {
                             PlayerMenu::Ready
                         },
                         grab_input: None,
+                        grab_timestamp: 0,
+                        grab_in_use: false,
                         grab_emulator_buttons: None,
+                        grab_hotkeys: None,
+                        edit_controls: None,
                     });
                     if let Some(ref mut first_player) = players[0] {
                         if first_player.menu == PlayerMenu::Ready {
@@ -484,15 +1416,19 @@ fn reduce(state: State, action: Action) -> State {
             let player_needs_setup_controls = state.player_needs_setup_controls(i);
             let player_has_game_controls = state.player_has_game_controls(i);
             let all_players_are_ready = state.all_players_are_ready();
+            let has_cores = i == 0 && !state.get_emulator().cores.is_empty();
             let mut players = state.players;
             if let Some(player) = players[i].as_mut() {
                 match player.menu {
-                    Ready => player.menu = Leave,
+                    Ready if has_cores => player.menu = Core,
+                    Ready | Core => player.menu = Leave,
                     Controls => {
-                        if player_needs_setup_controls || (i == 0 && !all_players_are_ready) {
-                            player.menu = Leave;
-                        } else {
+                        if !player_needs_setup_controls && (i != 0 || all_players_are_ready) {
                             player.menu = Ready;
+                        } else if has_cores {
+                            player.menu = Core;
+                        } else {
+                            player.menu = Leave;
                         }
                     }
                     ConsoleControls => player.menu = GameControls,
@@ -521,10 +1457,12 @@ fn reduce(state: State, action: Action) -> State {
             let player_needs_setup_controls = state.player_needs_setup_controls(i);
             let player_has_game_controls = state.player_has_game_controls(i);
             let all_players_are_ready = state.all_players_are_ready();
+            let has_cores = i == 0 && !state.get_emulator().cores.is_empty();
             let mut players = state.players;
             if let Some(player) = players[i].as_mut() {
                 match player.menu {
-                    Leave => {
+                    Leave if has_cores => player.menu = Core,
+                    Leave | Core => {
                         if player_needs_setup_controls || (i == 0 && !all_players_are_ready) {
                             player.menu = Controls;
                         } else {
@@ -556,6 +1494,11 @@ fn reduce(state: State, action: Action) -> State {
             use self::PlayerMenu::*;
 
             let rom = state.get_rom().file_name.clone();
+            let player_index = state.get_player_index(joystick_id, joystick_split);
+            let has_console_controls = state.get_player_controls(player_index, Console).is_some();
+            let has_game_controls = state.get_player_controls(player_index, Game).is_some();
+            let asks_emulator_buttons = state.asks_emulator_buttons();
+            let emulator_buttons = state.get_emulator_buttons(joystick_id, joystick_split);
             let mut screen = state.screen;
             let mut players = state.players;
             let mut game_configs = state.game_configs;
@@ -568,8 +1511,15 @@ fn reduce(state: State, action: Action) -> State {
                 joystick_split,
                 |i: usize, player: &mut Player| match player.menu {
                     Ready =>
-                        if i == 0 {
-                            player.grab_emulator_buttons = Some((None, None));
+                        if i == 0 && asks_emulator_buttons {
+                            // NOTE: the buttons are only asked when they are not saved yet
+                            match emulator_buttons.clone() {
+                                Some((hotkey, menu)) => {
+                                    player.grab_emulator_buttons = Some((Some(hotkey), Some(menu)));
+                                    player.menu = Waiting;
+                                }
+                                None => player.grab_emulator_buttons = Some((None, None)),
+                            }
                         } else {
                             player.menu = Waiting;
                         },
@@ -579,13 +1529,17 @@ fn reduce(state: State, action: Action) -> State {
                     }
                     Controls => player.menu = ConsoleControls,
                     ControlsExit => player.menu = Controls,
+                    Core => player.menu = CoreChoice,
+                    CoreChoice => player.menu = Core,
                     Leave => remove_player = Some(i),
-                    ConsoleControls => player.grab_input = Some((Console, Vec::new())),
+                    ConsoleControls =>
+                        edit_or_grab_controls(player, Console, has_console_controls, timestamp),
                     ClearConsoleControls => {
                         clear_game_config = Some(player.joystick);
                         player.menu = ControlsExit;
                     }
-                    GameControls => player.grab_input = Some((Game, Vec::new())),
+                    GameControls =>
+                        edit_or_grab_controls(player, Game, has_game_controls, timestamp),
                 }
             );
 
@@ -616,6 +1570,141 @@ fn reduce(state: State, action: Action) -> State {
                 ..state
             }
         }
+        NextControlEditor(timestamp, joystick, split, step) => {
+            let controls_len = state.get_controls().len();
+            let mut players = state.players;
+            modify_player!(players, joystick, split, |_, player: &mut Player| {
+                if let Some(editor) = player.edit_controls.as_mut() {
+                    editor.step(controls_len, step);
+                }
+            });
+
+            State {
+                timestamp,
+                players,
+                ..state
+            }
+        }
+        GoControlEditor(timestamp, joystick_id, split) => {
+            use self::EditorAction::*;
+            use self::EditorEntry::*;
+            use self::GrabControl::*;
+
+            let emulator_id = state.get_emulator().id.clone();
+            let rom = state.get_rom().file_name.clone();
+            let i = state.get_player_index(joystick_id, split);
+            let (target, entry, action) = match state.players[i].as_ref().unwrap().edit_controls {
+                Some(ref editor) => (editor.target, editor.entry, editor.action),
+                None => return state,
+            };
+            let mapping = state.get_player_controls(i, target);
+            let mut players = state.players;
+            let mut console_configs = state.console_configs;
+            let mut game_configs = state.game_configs;
+            let player = players[i].as_mut().unwrap();
+            match (entry, action, mapping) {
+                (All, _, _) => {
+                    player.edit_controls = None;
+                    player.grab_input = Some((target, Vec::new()));
+                    player.grab_timestamp = timestamp;
+                    player.grab_in_use = false;
+                }
+                (Exit, _, _) | (_, _, None) => player.edit_controls = None,
+                (Control(_), None, _) => {
+                    player.edit_controls.as_mut().unwrap().action = Some(Rebind)
+                }
+                (Control(_), Some(Rebind), Some(mapping)) => {
+                    player.grab_input = Some((target, mapping));
+                    player.grab_timestamp = timestamp;
+                    player.grab_in_use = false;
+                }
+                (Control(control), Some(Unassign), Some(mut mapping)) => {
+                    mapping[control] = JoystickEvent::Unassigned;
+                    player.edit_controls.as_mut().unwrap().action = None;
+
+                    let guid = state.joysticks[&player.joystick].guid;
+                    match target {
+                        Console => console_configs.insert(
+                            guid,
+                            player.joystick_split,
+                            emulator_id,
+                            mapping,
+                        ),
+                        Game => game_configs.insert(guid, player.joystick_split, rom, mapping),
+                    };
+                }
+                (Control(_), Some(Cancel), _) => {
+                    player.edit_controls.as_mut().unwrap().action = None
+                }
+            }
+
+            State {
+                timestamp,
+                players,
+                console_configs,
+                game_configs,
+                ..state
+            }
+        }
+        BackControlEditor(timestamp, joystick_id, split) => {
+            let mut players = state.players;
+            modify_player!(players, joystick_id, split, |_, player: &mut Player| {
+                match player.edit_controls.as_mut() {
+                    Some(editor) if editor.action.is_some() => editor.action = None,
+                    _ => player.edit_controls = None,
+                }
+            });
+
+            State {
+                timestamp,
+                players,
+                ..state
+            }
+        }
+        // NOTE: the next core of the emulator for the selected ROM
+        NextCore { timestamp, step } => {
+            let rom = state.get_rom().clone();
+            let names: Vec<String> = Some(String::new())
+                .into_iter()
+                .chain(state.get_emulator().cores.iter().map(|x| x.name.clone()))
+                .collect();
+            let index = names
+                .iter()
+                .position(|x| x == state.get_core_name(&rom))
+                .unwrap_or(0) as i32;
+            let name = names[(index + step).rem_euclid(names.len() as i32) as usize].clone();
+
+            let mut game_overrides = state.game_overrides;
+            let previous = game_overrides.remove(&rom.file_name);
+            let mut game_override = previous.clone().unwrap_or_default();
+            game_override.name = name;
+            if !game_override.is_empty() {
+                game_overrides.insert(rom.file_name.clone(), game_override);
+            }
+
+            let state = State {
+                timestamp,
+                game_overrides,
+                ..state
+            };
+            match command::has_placeholders(&state.get_command(&rom)) {
+                // NOTE: the previous core is kept, otherwise the game could never be launched again
+                Err(err) => {
+                    let mut game_overrides = state.game_overrides;
+                    match previous {
+                        Some(previous) => game_overrides.insert(rom.file_name.clone(), previous),
+                        None => game_overrides.remove(&rom.file_name),
+                    };
+
+                    State {
+                        screen: Screen::Error(format!("Invalid command: {}", err)),
+                        game_overrides,
+                        ..state
+                    }
+                }
+                Ok(_) => state,
+            }
+        }
         BindPlayerJoystickEvent(timestamp, i, event) => {
             use self::GrabControl::*;
 
@@ -629,33 +1718,50 @@ fn reduce(state: State, action: Action) -> State {
                 let guid = state.joysticks[&player.joystick].guid;
                 let mut save_mapping = None;
                 let (control, mut mapping) = player.grab_input.take().unwrap();
-                if mapping.len() < controls_len {
-                    if mapping.iter().any(|x| *x == event) {
-                        mapping.push(JoystickEvent::Unassigned);
-                    } else {
+                let editor_control = match player.edit_controls {
+                    Some(ControlEditor {
+                        entry: EditorEntry::Control(x),
+                        action: Some(EditorAction::Rebind),
+                        ..
+                    }) => Some(x),
+                    _ => None,
+                };
+                if let Some(index) = editor_control {
+                    // NOTE: the control that had the event loses it
+                    for x in mapping.iter_mut().filter(|x| **x == event) {
+                        *x = JoystickEvent::Unassigned;
+                    }
+                    mapping[index] = event;
+                    player.edit_controls.as_mut().unwrap().action = None;
+                    save_mapping = Some((control, mapping));
+                } else if mapping.len() < controls_len {
+                    // NOTE: the event of the previous control goes back to it, an event used
+                    //       already by another control is ignored
+                    let in_use = event != JoystickEvent::Unassigned && mapping.contains(&event);
+                    let back = in_use && mapping.last() == Some(&event);
+                    if back {
+                        mapping.pop();
+                    } else if !in_use {
                         mapping.push(event);
                     }
+                    player.grab_timestamp = timestamp;
+                    player.grab_in_use = in_use && !back;
 
                     if mapping.len() == controls_len {
                         save_mapping = Some((control, mapping));
                     } else {
                         player.grab_input = Some((control, mapping));
                     }
+                }
 
-                    match save_mapping {
-                        Some((Console, mapping)) => {
-                            console_configs.insert(
-                                guid,
-                                player.joystick_split,
-                                emulator_id,
-                                mapping,
-                            );
-                        }
-                        Some((Game, mapping)) => {
-                            game_configs.insert(guid, player.joystick_split, rom, mapping);
-                        }
-                        _ => {}
+                match save_mapping {
+                    Some((Console, mapping)) => {
+                        console_configs.insert(guid, player.joystick_split, emulator_id, mapping);
                     }
+                    Some((Game, mapping)) => {
+                        game_configs.insert(guid, player.joystick_split, rom, mapping);
+                    }
+                    _ => {}
                 }
             }
 
@@ -667,6 +1773,25 @@ fn reduce(state: State, action: Action) -> State {
                 ..state
             }
         }
+        // NOTE: the previous controls are kept when a grab waited too long
+        CancelGrab(timestamp) => {
+            let mut players = state.players;
+            for player in players.iter_mut().flatten() {
+                if player.grab_input.is_some() && timestamp >= player.grab_timestamp + GRAB_TIMEOUT
+                {
+                    player.grab_input = None;
+                    if let Some(editor) = player.edit_controls.as_mut() {
+                        editor.action = None;
+                    }
+                }
+            }
+
+            State {
+                timestamp,
+                players,
+                ..state
+            }
+        }
         UpdateJoystickLastAction(timestamp, joystick_id, split_index) => {
             let mut last_joystick_action = state.last_joystick_action;
             last_joystick_action.insert((joystick_id, split_index), timestamp);
@@ -677,8 +1802,29 @@ fn reduce(state: State, action: Action) -> State {
                 ..state
             }
         }
+        // NOTE: the hotkey and menu buttons are asked again even if they are saved
+        AskEmulatorButtons(timestamp) => {
+            let mut players = state.players;
+
+            if let Some(player) = players[0].as_mut() {
+                player.grab_emulator_buttons = Some((None, None));
+                player.grab_hotkeys = None;
+            }
+
+            State {
+                timestamp,
+                players,
+                ..state
+            }
+        }
         BindEmulatorButton(timestamp, event) => {
+            let has_hotkeys = !state.get_emulator().hotkeys.is_empty();
+            let config_key = state.players[0]
+                .as_ref()
+                .map(|x| (state.joysticks[&x.joystick].guid, x.joystick_split))
+                .map(|(guid, split)| (guid, split, state.hotkey_config_key(&guid, split)));
             let mut players = state.players;
+            let mut hotkey_configs = state.hotkey_configs;
 
             if let Some(player) = players[0].as_mut() {
                 let (hotkey, menu) = player.grab_emulator_buttons.take().unwrap();
@@ -686,14 +1832,60 @@ fn reduce(state: State, action: Action) -> State {
                 if hotkey.is_none() {
                     player.grab_emulator_buttons = Some((Some(event), menu));
                 } else if menu.is_none() {
+                    if let (Some((guid, split, key)), Some(hotkey)) = (config_key, hotkey.clone()) {
+                        hotkey_configs.insert(guid, split, key, vec![hotkey, event.clone()]);
+                    }
                     player.grab_emulator_buttons = Some((hotkey, Some(event)));
+                    if has_hotkeys {
+                        player.grab_hotkeys = Some(Vec::new());
+                    } else {
+                        player.menu = PlayerMenu::Waiting;
+                    }
+                }
+            }
+
+            State {
+                timestamp,
+                players,
+                hotkey_configs,
+                ..state
+            }
+        }
+        BindEmulatorHotkey(timestamp, event) => {
+            let hotkeys: Vec<_> = state
+                .get_emulator()
+                .hotkeys
+                .iter()
+                .map(|(key, _)| key.clone())
+                .collect();
+            let mut players = state.players;
+            let mut hotkey_actions = state.hotkey_actions;
+
+            if let Some(player) = players[0].as_mut() {
+                let guid = state.joysticks[&player.joystick].guid;
+                let mut mapping = player.grab_hotkeys.take().unwrap_or_default();
+
+                if mapping.len() < hotkeys.len() {
+                    mapping.push(event);
+                }
+                if mapping.len() == hotkeys.len() {
+                    for (key, event) in hotkeys.into_iter().zip(mapping.iter()) {
+                        hotkey_actions.insert(
+                            guid,
+                            player.joystick_split,
+                            key,
+                            vec![event.clone()],
+                        );
+                    }
                     player.menu = PlayerMenu::Waiting;
                 }
+                player.grab_hotkeys = Some(mapping);
             }
 
             State {
                 timestamp,
                 players,
+                hotkey_actions,
                 ..state
             }
         }
@@ -711,6 +1903,7 @@ pub struct Store {
     queue: Vec<StoreAction>,
 }
 
+#[allow(clippy::large_enum_variant)]
 enum StoreAction {
     Simple(Action),
     Thunk(Box<dyn Fn(&mut Store)>),
@@ -746,6 +1939,15 @@ impl Store {
                 command: vec!["retroarch".to_string()],
                 extensions: vec!["pce".to_string()],
                 exclude: vec!["syscard3.pce".to_string()],
+                max_depth: 0,
+                archive_launch: ArchiveLaunch::Archive,
+                generate_m3u: false,
+                preferred_regions: vec![],
+                dat_files: vec![],
+                media: None,
+                hotkeys: default_hotkeys(),
+                backend: BackendKind::RetroArch,
+                cores: vec![],
             },
             Emulator {
                 id: "md".to_string(),
@@ -764,6 +1966,15 @@ impl Store {
                 command: vec!["retroarch".to_string()],
                 extensions: vec!["bin".to_string(), "smd".to_string()],
                 exclude: vec![],
+                max_depth: 0,
+                archive_launch: ArchiveLaunch::Archive,
+                generate_m3u: false,
+                preferred_regions: vec![],
+                dat_files: vec![],
+                media: None,
+                hotkeys: default_hotkeys(),
+                backend: BackendKind::RetroArch,
+                cores: vec![],
             },
         ];
 
@@ -774,6 +1985,10 @@ impl Store {
             page_index: 0,
             page_count: 1,
             emulator_selected: 0,
+            virtual_system: None,
+            library: vec![],
+            folder: String::new(),
+            folder_positions: HashMap::new(),
             roms: Ok(vec![]),
             rom_selected: -1,
             rom_count: 0,
@@ -782,6 +1997,19 @@ impl Store {
             players: [None, None, None, None, None, None, None, None, None, None],
             console_configs: JoystickConfig::new(),
             game_configs: JoystickConfig::new(),
+            hotkey_configs: JoystickConfig::new(),
+            hotkey_actions: JoystickConfig::new(),
+            game_overrides: HashMap::new(),
+            button_pressed: HashMap::new(),
+            search: None,
+            favorites: HashMap::new(),
+            history: HashMap::new(),
+            sort_mode: SortMode::Name,
+            key_map: KeyMap::default(),
+            autoconfig_dirs: vec![],
+            autoconfig_export_dir: None,
+            joystick_names: HashMap::new(),
+            keep_alive: false,
         }
     }
 
@@ -838,6 +2066,17 @@ impl Store {
                 emulators: state.emulators.clone(),
                 console_configs: state.console_configs.clone(),
                 game_configs: state.game_configs.clone(),
+                favorites: state.favorites.clone(),
+                history: state.history.clone(),
+                sort_mode: state.sort_mode,
+                key_map: state.key_map.clone(),
+                autoconfig_dirs: state.autoconfig_dirs.clone(),
+                autoconfig_export_dir: state.autoconfig_export_dir.clone(),
+                joystick_names: state.joystick_names.clone(),
+                hotkey_configs: state.hotkey_configs.clone(),
+                hotkey_actions: state.hotkey_actions.clone(),
+                game_overrides: state.game_overrides.clone(),
+                keep_alive: state.keep_alive,
             };
             debug!("state dumped to: {:?}", save_state);
 
@@ -864,6 +2103,44 @@ impl Store {
     }
 }
 
+/// The hotkeys of RetroArch given to the emulators of the initial state
+fn default_hotkeys() -> Vec<(String, String)> {
+    [
+        ("exit_emulator", "Exit"),
+        ("save_state", "Save state"),
+        ("load_state", "Load state"),
+        ("state_slot_increase", "State slot +"),
+        ("state_slot_decrease", "State slot -"),
+        ("toggle_fast_forward", "Fast forward"),
+        ("rewind", "Rewind"),
+        ("pause_toggle", "Pause"),
+        ("screenshot", "Screenshot"),
+    ]
+    .iter()
+    .map(|&(key, label)| (key.to_string(), label.to_string()))
+    .collect()
+}
+
+/// Open the editor of the controls when they are set up already, otherwise bind them all
+fn edit_or_grab_controls(
+    player: &mut Player,
+    target: GrabControl,
+    has_controls: bool,
+    timestamp: u32,
+) {
+    if has_controls {
+        player.edit_controls = Some(ControlEditor {
+            target,
+            entry: EditorEntry::All,
+            action: None,
+        });
+    } else {
+        player.grab_input = Some((target, Vec::new()));
+        player.grab_timestamp = timestamp;
+        player.grab_in_use = false;
+    }
+}
+
 /// Store's middlewares
 fn trigger_middleware(store: &mut Store, action: Action) -> Option<Action> {
     use self::Action::*;
@@ -872,8 +2149,11 @@ fn trigger_middleware(store: &mut Store, action: Action) -> Option<Action> {
         &Initialize(timestamp, ..) | &NextEmulator { timestamp, .. } => {
             store.dispatch_thunk(Box::new(|store: &mut Store| {
                 let roms = {
-                    let emulator = store.get_state().get_emulator();
-                    get_roms(&emulator.path, &emulator.extensions, &emulator.exclude)
+                    let state = store.get_state();
+                    match state.virtual_system {
+                        Some(virtual_system) => get_virtual_roms(state, virtual_system),
+                        None => get_roms(state.get_emulator()),
+                    }
                 };
                 store.dispatch(LoadRoms { roms })
             }));
@@ -882,7 +2162,11 @@ fn trigger_middleware(store: &mut Store, action: Action) -> Option<Action> {
             Some(action)
         }
         &LaunchGame(timestamp, joystick, joystick_split) => {
-            store.dispatch(AddPlayer(timestamp, joystick, joystick_split));
+            // NOTE: an invalid command shows an error instead of the players
+            let state = store.get_state();
+            if command::has_placeholders(&state.get_command(state.get_rom())).is_ok() {
+                store.dispatch(AddPlayer(timestamp, joystick, joystick_split));
+            }
 
             Some(action)
         }
@@ -890,25 +2174,116 @@ fn trigger_middleware(store: &mut Store, action: Action) -> Option<Action> {
     }
 }
 
-fn get_roms(path: &str, extensions: &[String], exclude: &[String]) -> Result<Vec<Rom>, String> {
-    let resolved_path = path.replace("~", dirs::home_dir().unwrap().to_str().unwrap());
-    let mut roms = std::fs::read_dir(resolved_path)
-        .or_else(|x| Err(format!("{}", x)))?
+fn get_roms(emulator: &Emulator) -> Result<Vec<Rom>, String> {
+    let resolved_path = emulator
+        .path
+        .replace("~", dirs::home_dir().unwrap().to_str().unwrap());
+    let mut roms = Vec::new();
+    scan_folder(
+        Path::new(&resolved_path),
+        "",
+        emulator.max_depth,
+        &emulator.extensions,
+        &emulator.exclude,
+        &mut roms,
+    )?;
+    if !emulator.dat_files.is_empty() {
+        verify_roms(&mut roms, &emulator.dat_files);
+    }
+    load_gamelists(Path::new(&resolved_path), &mut roms);
+    for rom in roms.iter_mut() {
+        rom.emulator_id = emulator.id.clone();
+    }
+
+    if !emulator.preferred_regions.is_empty() {
+        roms = hide_other_regions(roms, &emulator.preferred_regions);
+    }
+
+    Ok(roms)
+}
+
+/// The ROMs of all the emulators that belong to a virtual system, without their folders (they are
+/// sorted when loaded)
+fn get_virtual_roms(state: &State, virtual_system: VirtualSystem) -> Result<Vec<Rom>, String> {
+    let mut roms = Vec::new();
+
+    for emulator in state.emulators.iter() {
+        let is_empty = match virtual_system {
+            VirtualSystem::Favorites => state.favorites.get(&emulator.id).map(|x| x.is_empty()),
+            VirtualSystem::RecentlyPlayed => state.history.get(&emulator.id).map(|x| x.is_empty()),
+        };
+        if is_empty != Some(false) {
+            continue;
+        }
+
+        match get_roms(emulator) {
+            Ok(emulator_roms) => roms.extend(
+                emulator_roms
+                    .into_iter()
+                    .filter(|x| match virtual_system {
+                        VirtualSystem::Favorites => state.is_favorite(x),
+                        VirtualSystem::RecentlyPlayed => state.get_play_stats(x).is_some(),
+                    })
+                    .map(|x| Rom {
+                        folder: String::new(),
+                        ..x
+                    }),
+            ),
+            Err(err) => warn!("could not list ROMs of {}: {}", emulator.name, err),
+        }
+    }
+
+    Ok(roms)
+}
+
+fn scan_folder(
+    dir: &Path,
+    folder: &str,
+    depth: u32,
+    extensions: &[String],
+    exclude: &[String],
+    roms: &mut Vec<Rom>,
+) -> Result<(), String> {
+    let paths = std::fs::read_dir(dir)
+        .map_err(|x| format!("{}", x))?
         .map(|x| x.unwrap().path())
-        .filter(|x| x.is_file())
-        .filter(|x| {
-            x.extension()
-                .map(|x| x.to_str().unwrap())
-                .map(|x| extensions.iter().any(|y| y == x))
-                == Some(true)
-        })
-        .filter(|x| {
-            x.file_name()
-                .map(|x| x.to_str().unwrap())
-                .map(|x| exclude.iter().any(|y| y == x))
-                == Some(false)
-        })
-        .map(|x| Rom {
+        .collect::<Vec<_>>();
+
+    if depth > 0 {
+        for x in paths.iter().filter(|x| x.is_dir()) {
+            let name = x.file_name().map(|x| x.to_str().unwrap()).unwrap();
+            let sub_folder = if folder.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}/{}", folder, name)
+            };
+            if let Err(err) = scan_folder(x, &sub_folder, depth - 1, extensions, exclude, roms) {
+                warn!("could not scan folder {}: {}", x.display(), err);
+            }
+        }
+    }
+
+    let mut playlists = Vec::new();
+    let mut in_playlist = HashSet::new();
+    for x in paths.iter().filter(|x| x.is_file() && disc::is_playlist(x)) {
+        let file_name = x.file_name().map(|x| x.to_str().unwrap()).unwrap();
+        let extension = x.extension().map(|x| x.to_str().unwrap()).unwrap();
+        if exclude.iter().any(|y| y == file_name) || !extensions.iter().any(|y| y == extension) {
+            continue;
+        }
+
+        match disc::read_playlist(x) {
+            Ok(discs) => {
+                in_playlist.extend(discs.iter().cloned());
+                playlists.push((x, discs));
+            }
+            Err(err) => warn!("could not read playlist {}: {}", x.display(), err),
+        }
+    }
+
+    let mut folder_roms: Vec<Rom> = Vec::new();
+    for (x, discs) in playlists {
+        folder_roms.push(Rom {
             path: x.to_str().unwrap().to_string(),
             name: x
                 .file_stem()
@@ -920,9 +2295,287 @@ fn get_roms(path: &str, extensions: &[String], exclude: &[String]) -> Result<Vec
                 .map(|x| x.to_str().unwrap())
                 .unwrap()
                 .to_string(),
-        })
-        .collect::<Vec<_>>();
-    roms.sort_by(|a, b| a.name.cmp(&b.name));
+            discs: discs
+                .iter()
+                .map(|x| x.to_str().unwrap().to_string())
+                .collect(),
+            folder: folder.to_string(),
+            ..Rom::default()
+        });
+    }
 
-    Ok(roms)
+    let mut disc_sets: HashMap<String, Vec<(u32, Rom)>> = HashMap::new();
+    for x in paths
+        .iter()
+        .filter(|x| x.is_file() && !disc::is_playlist(x) && !in_playlist.contains(*x))
+    {
+        let file_name = x.file_name().map(|x| x.to_str().unwrap()).unwrap();
+        if exclude.iter().any(|y| y == file_name) {
+            continue;
+        }
+
+        let archive_entry = if x
+            .extension()
+            .map(|x| x.to_str().unwrap())
+            .map(|x| extensions.iter().any(|y| y == x))
+            == Some(true)
+        {
+            None
+        } else if archive::is_archive(x) {
+            match archive::find_file(x, extensions) {
+                Ok(Some(entry)) => Some(entry),
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!("could not read archive {}: {}", x.display(), err);
+                    continue;
+                }
+            }
+        } else {
+            continue;
+        };
+
+        let name = match archive_entry {
+            Some(ref entry) => Path::new(entry).file_stem(),
+            None => x.file_stem(),
+        }
+        .map(|x| x.to_str().unwrap())
+        .unwrap()
+        .to_string();
+
+        let rom = Rom {
+            path: x.to_str().unwrap().to_string(),
+            name,
+            file_name: file_name.to_string(),
+            archive_entry,
+            folder: folder.to_string(),
+            ..Rom::default()
+        };
+
+        match disc::split_disc(&rom.name) {
+            Some((game, number)) => disc_sets.entry(game).or_default().push((number, rom)),
+            None => folder_roms.push(rom),
+        }
+    }
+
+    for (game, mut discs) in disc_sets {
+        discs.sort_by_key(|&(number, _)| number);
+        let mut rom = discs[0].1.clone();
+        if discs.len() > 1 {
+            rom.name = game;
+            rom.discs = discs.into_iter().map(|(_, x)| x.path).collect();
+        }
+        folder_roms.push(rom);
+    }
+
+    for rom in folder_roms.iter_mut() {
+        let name = rom.name.clone();
+        rom.parse_name(&name);
+    }
+    roms.extend(folder_roms);
+
+    Ok(())
+}
+
+/// Set the status of the ROMs using the DAT files. The name of a verified ROM is replaced by the
+/// canonical name of the DAT
+fn verify_roms(roms: &mut [Rom], dat_files: &[String]) {
+    let dats = Dat::load(dat_files);
+    let mut hash_cache = HashCache::load();
+
+    for rom in roms
+        .iter_mut()
+        .filter(|x| !disc::is_playlist(Path::new(&x.path)))
+    {
+        let hashes = match hash_cache.get(&rom.path, rom.archive_entry.as_deref()) {
+            Ok(hashes) => hashes,
+            Err(err) => {
+                warn!("could not hash {}: {}", rom.path, err);
+                continue;
+            }
+        };
+        let file_name = match rom.archive_entry {
+            Some(ref entry) => Path::new(entry).file_name().unwrap().to_str().unwrap(),
+            None => &rom.file_name,
+        };
+        let (status, canonical_name) = dat::verify(&dats, file_name, &hashes);
+
+        if status == RomStatus::Verified && rom.discs.is_empty() {
+            rom.parse_name(canonical_name.as_ref().unwrap());
+        }
+        rom.status = status;
+        rom.canonical_name = canonical_name;
+    }
+
+    if let Err(err) = hash_cache.save() {
+        error!("could not write hash cache: {}", err);
+    }
+}
+
+/// Attach the metadata of the gamelist.xml files of the folders (and their parents) to the ROMs
+fn load_gamelists(root: &Path, roms: &mut [Rom]) {
+    let mut folders = BTreeSet::new();
+    folders.insert("");
+    for rom in roms.iter() {
+        let mut folder = rom.folder.as_str();
+        while !folder.is_empty() {
+            folders.insert(folder);
+            folder = folder.rsplit_once('/').map_or("", |x| x.0);
+        }
+    }
+
+    let mut games = HashMap::new();
+    for folder in folders {
+        games.extend(gamelist::load(&root.join(folder)));
+    }
+
+    if games.is_empty() {
+        return;
+    }
+
+    for rom in roms.iter_mut() {
+        rom.info = games.get(Path::new(&rom.path)).cloned();
+    }
+}
+
+/// Keep only one ROM per game (same folder, title and tags): the one with the best region
+/// according to the preferred regions, then the verified dump and the latest revision
+fn hide_other_regions(roms: Vec<Rom>, preferred_regions: &[String]) -> Vec<Rom> {
+    let score = |rom: &Rom| {
+        (
+            rom.regions
+                .iter()
+                .filter_map(|x| preferred_regions.iter().position(|y| y == x))
+                .min()
+                .unwrap_or(preferred_regions.len()),
+            !rom.flags.iter().any(|x| x == "!"),
+            cmp::Reverse(rom.revision.as_deref().map(romname::version_key)),
+            cmp::Reverse(rom.version.as_deref().map(romname::version_key)),
+            // NOTE: the ROMs are in the order of the folder
+            rom.name.clone(),
+        )
+    };
+    let mut best: HashMap<(&str, String, &[String]), usize> = HashMap::new();
+
+    for (i, rom) in roms.iter().enumerate() {
+        let key = (
+            rom.folder.as_str(),
+            rom.title.to_lowercase(),
+            rom.tags.as_slice(),
+        );
+        match best.get(&key) {
+            Some(&j) if score(&roms[j]) <= score(rom) => {}
+            _ => {
+                best.insert(key, i);
+            }
+        }
+    }
+
+    let keep: HashSet<usize> = best.values().cloned().collect();
+    roms.into_iter()
+        .enumerate()
+        .filter(|(i, _)| keep.contains(i))
+        .map(|(_, x)| x)
+        .collect()
+}
+
+/// Build the entries of the list for a folder of the library: a link to the parent folder,
+/// the sub-folders containing ROMs and then the ROMs themselves
+fn list_folder(library: &[Rom], folder: &str) -> Vec<Rom> {
+    let mut entries = Vec::new();
+
+    if !folder.is_empty() {
+        let parent = folder.rsplit_once('/').map_or("", |x| x.0);
+
+        entries.push(Rom {
+            path: String::new(),
+            name: "..".to_string(),
+            file_name: "..".to_string(),
+            title: "..".to_string(),
+            folder: folder.to_string(),
+            kind: RomKind::Folder(parent.to_string()),
+            ..Rom::default()
+        });
+    }
+
+    let prefix = if folder.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", folder)
+    };
+    let sub_folders: BTreeSet<&str> = library
+        .iter()
+        .filter(|x| !x.folder.is_empty() && x.folder.starts_with(&prefix))
+        .map(|x| x.folder[prefix.len()..].split('/').next().unwrap())
+        .filter(|x| !x.is_empty())
+        .collect();
+    for name in sub_folders {
+        entries.push(Rom {
+            path: String::new(),
+            name: name.to_string(),
+            file_name: name.to_string(),
+            title: name.to_string(),
+            folder: folder.to_string(),
+            kind: RomKind::Folder(format!("{}{}", prefix, name)),
+            ..Rom::default()
+        });
+    }
+
+    entries.extend(library.iter().filter(|x| x.folder == folder).cloned());
+
+    entries
+}
+
+/// Build the entries of the list: the ROMs matching the search or else the entries of the folder
+fn list_view(library: &[Rom], folder: &str, search: &Option<String>) -> Vec<Rom> {
+    match *search {
+        Some(ref text) if !text.is_empty() => search_library(library, text),
+        _ => list_folder(library, folder),
+    }
+}
+
+/// The files of the library, in all the folders, whose name contains the text (case insensitive)
+fn search_library(library: &[Rom], text: &str) -> Vec<Rom> {
+    let text = text.to_lowercase();
+
+    library
+        .iter()
+        .filter(|x| x.display_name().to_lowercase().contains(&text))
+        .cloned()
+        .collect()
+}
+
+/// The letter under which a ROM is listed, '#' for anything that is not a letter
+fn initial(rom: &Rom) -> char {
+    match rom.display_name().chars().next() {
+        Some(c) if c.is_alphabetic() => c.to_ascii_uppercase(),
+        _ => '#',
+    }
+}
+
+/// Index of the first ROM of the next (or previous) letter of the list
+fn jump_to_letter(roms: &[Rom], index: i32, step: i32) -> Option<i32> {
+    let initials: Vec<char> = roms.iter().map(initial).collect();
+    if initials.is_empty() {
+        return None;
+    }
+    let index = cmp::min(index as usize, initials.len() - 1);
+    let group_start = |i: usize| {
+        initials[..i]
+            .iter()
+            .rposition(|&x| x != initials[i])
+            .map_or(0, |x| x + 1)
+    };
+
+    if step > 0 {
+        initials[index..]
+            .iter()
+            .position(|&x| x != initials[index])
+            .map(|x| (index + x) as i32)
+    } else if group_start(index) < index {
+        Some(group_start(index) as i32)
+    } else if index > 0 {
+        Some(group_start(index - 1) as i32)
+    } else {
+        None
+    }
 }